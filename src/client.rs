@@ -0,0 +1,418 @@
+//
+// Kiho punch API client abstraction.
+//
+// Command logic in `main.rs` talks only to `KihoClient` trait, so the real HTTP backend can be
+// swapped to the in-memory fake when testing commands without the real v3.kiho.fi service.
+//
+
+use std::cell::{Cell, RefCell};
+
+use chrono::prelude::*;
+
+use crate::error::KihoError;
+use crate::punch::{KihoResponse, Punch, PunchType};
+use crate::{STAMP_FORMAT, USER_AGENT};
+
+// Kiho pages punch lists, so longer lists are fetched using multiple requests of `PAGE_SIZE` lines.
+// Requests stop after `MAX_PAGES` pages whatever was asked, to avoid hammering the API by accident.
+const PAGE_SIZE: u32 = 100;
+const MAX_PAGES: u32 = 50;
+
+// Query parameter names used for limiting punch lines by their timestamp.
+const PARAM_FROM: &str = "timestamp[gte]";
+const PARAM_TO:   &str = "timestamp[lt]";
+
+/// Which punch lines to list. Timestamp window is half-open, i.e `from <= timestamp < to`.
+#[derive(Clone, Copy, Default)]
+pub struct PunchFilter {
+    pub punch_type: Option<PunchType>,
+    pub from:       Option<DateTime<FixedOffset>>,
+    pub to:         Option<DateTime<FixedOffset>>,
+}
+impl PunchFilter {
+    pub fn matches(&self, punch: &Punch) -> bool {
+        self.punch_type.is_none_or(|pt| punch.typ == pt)
+            && self.from.is_none_or(|from| punch.timestamp >= from)
+            && self.to.is_none_or(|to| punch.timestamp < to)
+    }
+}
+
+/// Which page of punch lines to list. Results are always in descending timestamp order.
+/// Pages are numbered from 1 onwards.
+pub struct PunchQuery {
+    pub filter:    PunchFilter,
+    pub page_size: u32,
+    pub page:      u32,
+}
+
+pub trait KihoClient {
+    /// Lists one page of punch lines matching the query, latest first.
+    fn list_punches(&self, query: &PunchQuery) -> Result<Vec<Punch>, KihoError>;
+    /// Creates new punch line from `{ "newPunch": {...} }` JSON body and returns it as stored by Kiho.
+    fn create_punch(&self, new_punch: &serde_json::Value) -> Result<Punch, KihoError>;
+    /// Changes fields given in `{ "punch": {...} }` JSON body and returns the punch line as stored by Kiho.
+    fn update_punch(&self, id: u64, changes: &serde_json::Value) -> Result<Punch, KihoError>;
+    /// Removes the punch line for good.
+    fn delete_punch(&self, id: u64) -> Result<(), KihoError>;
+
+    /// Lists up to `count` latest punch lines matching the filter, or all of them if `count` is not given.
+    /// Follows Kiho's paging as long as needed, but never fetches more than `MAX_PAGES` pages.
+    fn list_all_punches(&self, filter: PunchFilter, count: Option<u32>, verbose: u8) -> Result<Vec<Punch>, KihoError> {
+        let max_count = PAGE_SIZE * MAX_PAGES;
+        if count.is_some_and(|cnt| cnt > max_count) {
+            msgln!("WARNING: Fetching only {} punch lines instead of {}", max_count, count.unwrap_or_default());
+        }
+        let count = count.unwrap_or(max_count).min(max_count) as usize;
+        let page_size = PAGE_SIZE.min(count as u32);
+        let mut punches: Vec<Punch> = Vec::new();
+        let mut page = 1;
+        while punches.len() < count {
+            if page > MAX_PAGES {
+                msgln!("WARNING: Stopped fetching after {} pages, punch lines might be missing", MAX_PAGES);
+                break;
+            }
+            let query = PunchQuery { filter, page_size, page };
+            let batch = self.list_punches(&query)?;
+            let batch_len = batch.len();
+            // Descending order means there's nothing more to find after passing the window start
+            let passed_from = filter.from.is_some_and(|from| batch.last().is_some_and(|pl| pl.timestamp < from));
+            punches.extend(batch.into_iter().filter(|pl| filter.matches(pl)));
+            if verbose > 0 {
+                msgln!("{} :: Fetched page {}, got {} punch line(s)", Local::now().format(STAMP_FORMAT), page, punches.len().min(count));
+            }
+            if batch_len < page_size as usize || passed_from {
+                break;
+            }
+            page += 1;
+        }
+        punches.truncate(count);
+        Ok(punches)
+    }
+}
+
+
+/// The real thing, i.e Kiho HTTP API used with `reqwest`.
+pub struct HttpClient {
+    api_key: String,
+    api_url: String,
+    verbose: u8,
+    http:    reqwest::blocking::Client,
+}
+impl HttpClient {
+    pub fn new(api_key: &str, api_url: &str, verbose: u8) -> Self {
+        let http = reqwest::blocking::Client::builder()
+            .user_agent(USER_AGENT)
+            .build()
+            .expect("Building HTTP client failed");
+        HttpClient {
+            api_key: api_key.to_string(),
+            api_url: api_url.to_string(),
+            verbose,
+            http,
+        }
+    }
+
+    /// URL of a single punch line, e.g `https://v3.kiho.fi/api/v1/punch/123`.
+    fn punch_url(&self, id: u64) -> String {
+        format!("{}/{}", self.api_url.trim_end_matches('/'), id)
+    }
+
+    /// Checks HTTP status of the response and parses its JSON body.
+    /// Non-successful responses are turned into errors containing the server's error body.
+    fn read_json_response(&self, resp: reqwest::blocking::Response, method: &str) -> Result<serde_json::Value, KihoError> {
+        let resp = self.check_status(resp, method)?;
+        let json: serde_json::Value = resp.json()?;
+        if self.verbose > 0 {
+            msgln!("PUNCH {} RESPONSE JSON:", method);
+            msgln!("{:#}", json);
+        }
+        Ok(json)
+    }
+
+    /// Turns non-successful responses into errors containing the server's error body.
+    fn check_status(&self, resp: reqwest::blocking::Response, method: &str) -> Result<reqwest::blocking::Response, KihoError> {
+        let status = resp.status();
+        msgln!("{} :: HTTP response: {}", Local::now().format(STAMP_FORMAT), status);
+        if self.verbose > 1 {
+            msgln!("PUNCH {} RESPONSE HEADERS:", method);
+            msgln!("{:#?}", resp.headers());
+            msgln!("{:#?}", resp);
+        }
+        if !status.is_success() {
+            let body = resp.text().unwrap_or_default();
+            return Err(match status {
+                reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => KihoError::Unauthorized(status),
+                _ if status.is_client_error() => KihoError::Validation(status, body),
+                _                             => KihoError::Server(status, body),
+            });
+        }
+        Ok(resp)
+    }
+}
+impl KihoClient for HttpClient {
+    fn list_punches(&self, query: &PunchQuery) -> Result<Vec<Punch>, KihoError> {
+        msgln!("{} :: Starting HTTP GET request...", Local::now().format(STAMP_FORMAT));
+        let mut params = vec![
+            // ("mode",  String::from("latest")),           // Returns SINGLE `result` object instead of an ARRAY :/
+            ("orderBy",  String::from("timestamp DESC")),   // NOTE: Nowadays `+` means SPACE in URLs like `%20` used to be !
+            ("pageSize", query.page_size.to_string()),
+            ("page",     query.page.to_string()),
+        ];
+        if let Some(pt) = query.filter.punch_type {
+            params.push(("type", pt.to_string()));
+        }
+        if let Some(from) = query.filter.from {
+            params.push((PARAM_FROM, from.to_rfc3339()));
+        }
+        if let Some(to) = query.filter.to {
+            params.push((PARAM_TO, to.to_rfc3339()));
+        }
+        let request = self.http
+            .get(&self.api_url)
+            .query(&params)
+            .header(reqwest::header::AUTHORIZATION, &self.api_key)
+            // .header(reqwest::header::CONTENT_TYPE, "application/json") HTTP GET does NOT work if this is set!
+            .header(reqwest::header::ACCEPT, "application/json");
+            // .version(reqwest::Version::HTTP_2);
+        if self.verbose > 1 {
+            msgln!("PUNCH GET REQUEST:");
+            msgln!("{:#?}", request);
+            msgln!("PUNCH GET QUERY PARAMS:");
+            for (k,v) in &params {
+                msgln!("{k:>10}={v}")
+            }
+        }
+        let resp = request.send()?;
+        let json = self.read_json_response(resp, "GET")?;
+        let punch_lines: KihoResponse<Vec<Punch>> = serde_json::from_value(json)
+            .map_err(|err| KihoError::Json(err.to_string()))?;
+        Ok(punch_lines.result)
+    }
+
+    fn create_punch(&self, new_punch: &serde_json::Value) -> Result<Punch, KihoError> {
+        msgln!("{} :: Starting HTTP POST request...", Local::now().format(STAMP_FORMAT));
+        let request = self.http
+            .post(&self.api_url)
+            .json(new_punch)
+            .header(reqwest::header::AUTHORIZATION, &self.api_key)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(reqwest::header::ACCEPT, "application/json");
+            // .version(reqwest::Version::HTTP_2);
+        if self.verbose > 1 {
+            msgln!("PUNCH POST REQUEST:");
+            msgln!("{:#?}", request);
+        }
+        let resp = request.send()?;
+        let json = self.read_json_response(resp, "POST")?;
+        let punch: KihoResponse<Punch> = serde_json::from_value(json)
+            .map_err(|err| KihoError::Json(err.to_string()))?;
+        Ok(punch.result)
+    }
+
+    fn update_punch(&self, id: u64, changes: &serde_json::Value) -> Result<Punch, KihoError> {
+        msgln!("{} :: Starting HTTP PUT request...", Local::now().format(STAMP_FORMAT));
+        let request = self.http
+            .put(self.punch_url(id))
+            .json(changes)
+            .header(reqwest::header::AUTHORIZATION, &self.api_key)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(reqwest::header::ACCEPT, "application/json");
+        if self.verbose > 1 {
+            msgln!("PUNCH PUT REQUEST:");
+            msgln!("{:#?}", request);
+        }
+        let resp = request.send()?;
+        let json = self.read_json_response(resp, "PUT")?;
+        let punch: KihoResponse<Punch> = serde_json::from_value(json)
+            .map_err(|err| KihoError::Json(err.to_string()))?;
+        Ok(punch.result)
+    }
+
+    fn delete_punch(&self, id: u64) -> Result<(), KihoError> {
+        msgln!("{} :: Starting HTTP DELETE request...", Local::now().format(STAMP_FORMAT));
+        let request = self.http
+            .delete(self.punch_url(id))
+            .header(reqwest::header::AUTHORIZATION, &self.api_key)
+            .header(reqwest::header::ACCEPT, "application/json");
+        if self.verbose > 1 {
+            msgln!("PUNCH DELETE REQUEST:");
+            msgln!("{:#?}", request);
+        }
+        // Whatever Kiho returns in the body is not needed
+        self.check_status(request.send()?, "DELETE")?;
+        Ok(())
+    }
+}
+
+
+/// Lists one page of the given punch lines like Kiho API does, i.e latest first.
+fn list_page(punches: &[Punch], query: &PunchQuery) -> Vec<Punch> {
+    let mut punches: Vec<Punch> = punches.iter()
+        .filter(|pl| query.filter.matches(pl))
+        .cloned()
+        .collect();
+    punches.sort_by_key(|pl| std::cmp::Reverse((pl.timestamp, pl.id)));
+    let skip = (query.page.max(1) - 1) * query.page_size;
+    punches.into_iter()
+        .skip(skip as usize)
+        .take(query.page_size as usize)
+        .collect()
+}
+
+
+/// In-memory fake of the Kiho punch API. Stores created punches only for the lifetime of the process.
+pub struct MemoryClient {
+    punches: RefCell<Vec<Punch>>,
+    next_id: Cell<u64>,
+}
+impl MemoryClient {
+    pub fn new(punches: Vec<Punch>) -> Self {
+        let next_id = punches.iter().map(|pl| pl.id).max().unwrap_or(0) + 1;
+        MemoryClient {
+            punches: RefCell::new(punches),
+            next_id: Cell::new(next_id),
+        }
+    }
+}
+impl KihoClient for MemoryClient {
+    fn list_punches(&self, query: &PunchQuery) -> Result<Vec<Punch>, KihoError> {
+        Ok(list_page(&self.punches.borrow(), query))
+    }
+
+    fn create_punch(&self, new_punch: &serde_json::Value) -> Result<Punch, KihoError> {
+        let mut json = new_punch.get("newPunch")
+            .cloned()
+            .ok_or_else(|| KihoError::Validation(reqwest::StatusCode::BAD_REQUEST, String::from("`newPunch` missing")))?;
+        json["id"] = self.next_id.get().into();
+        let punch: Punch = serde_json::from_value(json)
+            .map_err(|err| KihoError::Validation(reqwest::StatusCode::BAD_REQUEST, err.to_string()))?;
+        self.next_id.set(punch.id + 1);
+        self.punches.borrow_mut().push(punch.clone());
+        Ok(punch)
+    }
+
+    fn update_punch(&self, id: u64, changes: &serde_json::Value) -> Result<Punch, KihoError> {
+        let mut punches = self.punches.borrow_mut();
+        let punch = punches.iter_mut()
+            .find(|pl| pl.id == id)
+            .ok_or_else(|| KihoError::Validation(reqwest::StatusCode::NOT_FOUND, format!("Punch {} not found", id)))?;
+        let mut json = serde_json::to_value(&*punch)
+            .map_err(|err| KihoError::Json(err.to_string()))?;
+        if let (Some(json), Some(changes)) = (json.as_object_mut(), changes["punch"].as_object()) {
+            json.extend(changes.clone());
+        }
+        *punch = serde_json::from_value(json)
+            .map_err(|err| KihoError::Validation(reqwest::StatusCode::BAD_REQUEST, err.to_string()))?;
+        Ok(punch.clone())
+    }
+
+    fn delete_punch(&self, id: u64) -> Result<(), KihoError> {
+        let mut punches = self.punches.borrow_mut();
+        let count = punches.len();
+        punches.retain(|pl| pl.id != id);
+        match punches.len() < count {
+            true  => Ok(()),
+            false => Err(KihoError::Validation(reqwest::StatusCode::NOT_FOUND, format!("Punch {} not found", id))),
+        }
+    }
+}
+
+
+/// Punch lines from the local journal, used when running with `--offline`.
+/// New punch lines cannot be created without Kiho API, so they end up in the offline queue instead.
+pub struct OfflineClient {
+    punches: Vec<Punch>,
+}
+impl OfflineClient {
+    pub fn new(punches: Vec<Punch>) -> Self {
+        OfflineClient { punches }
+    }
+}
+impl KihoClient for OfflineClient {
+    fn list_punches(&self, query: &PunchQuery) -> Result<Vec<Punch>, KihoError> {
+        Ok(list_page(&self.punches, query))
+    }
+
+    fn create_punch(&self, _new_punch: &serde_json::Value) -> Result<Punch, KihoError> {
+        Err(KihoError::Offline)
+    }
+
+    fn update_punch(&self, _id: u64, _changes: &serde_json::Value) -> Result<Punch, KihoError> {
+        Err(KihoError::Offline)
+    }
+
+    fn delete_punch(&self, _id: u64) -> Result<(), KihoError> {
+        Err(KihoError::Offline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Punch lines an hour apart, the latest one having the biggest id.
+    fn hourly_punches(count: u64, typ_of: impl Fn(u64) -> &'static str) -> Vec<Punch> {
+        let start = DateTime::parse_from_rfc3339("2024-09-02T08:00:00+03:00").unwrap();
+        (1..=count).map(|id| {
+            let timestamp = start + chrono::TimeDelta::hours(id as i64);
+            serde_json::from_value(json!({ "id": id, "type": typ_of(id), "timestamp": timestamp })).unwrap()
+        }).collect()
+    }
+
+    /// Serves pages like Kiho but leaves filtering to the caller, and counts the pages requested.
+    struct PagingClient {
+        punches: Vec<Punch>,
+        pages:   Cell<u32>,
+    }
+    impl KihoClient for PagingClient {
+        fn list_punches(&self, query: &PunchQuery) -> Result<Vec<Punch>, KihoError> {
+            self.pages.set(self.pages.get() + 1);
+            Ok(list_page(&self.punches, &PunchQuery { filter: PunchFilter::default(), ..*query }))
+        }
+        fn create_punch(&self, _new_punch: &serde_json::Value) -> Result<Punch, KihoError> {
+            unreachable!("Paging client only lists punch lines")
+        }
+        fn update_punch(&self, _id: u64, _changes: &serde_json::Value) -> Result<Punch, KihoError> {
+            unreachable!("Paging client only lists punch lines")
+        }
+        fn delete_punch(&self, _id: u64) -> Result<(), KihoError> {
+            unreachable!("Paging client only lists punch lines")
+        }
+    }
+
+    #[test]
+    fn count_fetched_across_pages() {
+        let client = MemoryClient::new(hourly_punches(250, |_| "LOGIN"));
+        let plines = client.list_all_punches(PunchFilter::default(), Some(230), 0).unwrap();
+        assert_eq!(plines.len(), 230);
+        assert_eq!(plines.first().map(|pl| pl.id), Some(250));
+        assert_eq!(plines.last().map(|pl| pl.id), Some(21));
+        assert_eq!(client.list_all_punches(PunchFilter::default(), None, 0).unwrap().len(), 250);
+    }
+
+    #[test]
+    fn fetching_stops_at_page_cap() {
+        // Every other punch line matches, so the count is never reached before the cap
+        let client = PagingClient {
+            punches: hourly_punches(u64::from(PAGE_SIZE * (MAX_PAGES + 2)), |id| if id % 2 == 0 { "LOGIN" } else { "LOGOUT" }),
+            pages:   Cell::new(0),
+        };
+        let filter = PunchFilter { punch_type: Some(PunchType::LOGIN), ..Default::default() };
+        let plines = client.list_all_punches(filter, None, 0).unwrap();
+        assert_eq!(client.pages.get(), MAX_PAGES);
+        assert_eq!(plines.len() as u32, PAGE_SIZE * MAX_PAGES / 2);
+        assert!(plines.iter().all(|pl| pl.typ == PunchType::LOGIN));
+    }
+
+    #[test]
+    fn fetching_stops_after_window_start() {
+        let punches = hourly_punches(300, |_| "LOGIN");
+        let from = punches[149].timestamp;
+        let client = PagingClient { punches, pages: Cell::new(0) };
+        let filter = PunchFilter { from: Some(from), ..Default::default() };
+        let plines = client.list_all_punches(filter, None, 0).unwrap();
+        assert_eq!(client.pages.get(), 2);
+        assert_eq!(plines.len(), 151);
+        assert_eq!(plines.last().map(|pl| pl.timestamp), Some(from));
+    }
+}
@@ -0,0 +1,96 @@
+//
+// Errors that can happen while talking to the Kiho API or writing local files.
+//
+
+use reqwest::StatusCode;
+
+#[derive(Debug)]
+pub enum KihoError {
+    /// Request could not be sent or response could not be read, e.g network or DNS failure.
+    /// Only connection failures and timeouts mean that Kiho API is unreachable.
+    Network(reqwest::Error),
+    /// Server refused the API key (401 Unauthorized or 403 Forbidden).
+    Unauthorized(StatusCode),
+    /// Server refused the request itself (other 4xx), with the server's error body.
+    Validation(StatusCode, String),
+    /// Server failed to handle the request (5xx or otherwise unexpected status), with the server's error body.
+    Server(StatusCode, String),
+    /// Response was not the JSON we expected.
+    Json(String),
+    /// Punch does not make sense after the latest one, e.g second LOGOUT in a row.
+    PunchState(String),
+    /// Local file could not be written, e.g exported CSV file.
+    Io(std::io::Error),
+    /// Kiho API is not used at all, i.e running with `--offline`.
+    Offline,
+    /// API key is not configured, or it could not be read from the configured command or file.
+    ApiKey(String),
+    /// Configuration is invalid or does not have what was asked for, e.g unknown cost centre name.
+    Config(String),
+}
+
+impl KihoError {
+    /// Distinct process exit code for each error kind, so that scripts can tell them apart.
+    /// Note that `clap` already uses exit code 2 for invalid command line arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            KihoError::PunchState(_)    => 1,
+            KihoError::Network(_)       => 3,
+            KihoError::Unauthorized(_)  => 4,
+            KihoError::Validation(_, _) => 5,
+            KihoError::Server(_, _)     => 6,
+            KihoError::Json(_)          => 7,
+            KihoError::Io(_)            => 8,
+            KihoError::ApiKey(_)        => 9,
+            KihoError::Config(_)        => 10,
+            KihoError::Offline          => 11,
+        }
+    }
+
+    /// Whether Kiho API could not be reached at all, so that punches are worth queuing to be sent later.
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            KihoError::Network(err) => err.is_connect() || err.is_timeout(),
+            KihoError::Offline      => true,
+            _                       => false,
+        }
+    }
+}
+
+impl std::fmt::Display for KihoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            KihoError::Network(err)              => write!(f, "HTTP request failed: {}", err),
+            KihoError::Unauthorized(status)      => write!(f, "Kiho API refused the API key ({}), check API key in the configuration", status),
+            KihoError::Validation(status, body)  => write!(f, "Kiho API refused the request ({}):\n{}", status, body),
+            KihoError::Server(status, body)      => write!(f, "Kiho API failed to handle the request ({}):\n{}", status, body),
+            KihoError::Json(msg)                 => write!(f, "Unexpected JSON response: {}", msg),
+            KihoError::PunchState(msg)           => write!(f, "{}", msg),
+            KihoError::Io(err)                   => write!(f, "File I/O failed: {}", err),
+            KihoError::Offline                   => write!(f, "Kiho API is not used when running offline"),
+            KihoError::ApiKey(msg)               => write!(f, "{}", msg),
+            KihoError::Config(msg)               => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for KihoError {}
+
+impl From<reqwest::Error> for KihoError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_decode() {
+            KihoError::Json(err.to_string())
+        } else if err.is_builder() {
+            // E.g invalid API URL, which is never going to work no matter how many times it's tried
+            KihoError::Config(format!("Invalid Kiho API request: {}", err))
+        } else {
+            KihoError::Network(err)
+        }
+    }
+}
+
+impl From<std::io::Error> for KihoError {
+    fn from(err: std::io::Error) -> Self {
+        KihoError::Io(err)
+    }
+}
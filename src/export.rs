@@ -0,0 +1,267 @@
+//
+// Exporting punch lines and paired sessions into files, e.g for spreadsheets or calendars.
+//
+
+use std::io::Write;
+
+use chrono::prelude::*;
+use chrono::TimeDelta;
+
+use crate::punch::Punch;
+use crate::session::{Session, SessionKind};
+use crate::{APP_NAME, APP_VERSION};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// Comma separated values with a header row
+    Csv,
+    /// iCalendar with an event per session, or a zero length event per punch line
+    Ics,
+}
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ics => "ics",
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ExportWhat {
+    /// Raw punch lines
+    Punches,
+    /// LOGIN/BREAK sessions paired with the punch line ending them
+    Sessions,
+}
+
+const CSV_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Quotes CSV field if needed, i.e if it contains separators, quotes or line breaks.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn csv_line(out: &mut dyn Write, fields: &[String]) -> std::io::Result<()> {
+    let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+    writeln!(out, "{}", line.join(","))
+}
+
+/// Cost centre ID, code, name, project and customer columns of the punch line.
+fn ccc_fields(punch: &Punch) -> Vec<String> {
+    let ccc = punch.customer_costcentre.as_ref();
+    vec![
+        punch.ccc_id().map(|id| id.to_string()).unwrap_or_default(),
+        ccc.and_then(|ccc| ccc.code.clone()).unwrap_or_default(),
+        punch.ccc_name().to_string(),
+        ccc.and_then(|ccc| ccc.project.as_ref()).and_then(|p| p.name.clone()).unwrap_or_default(),
+        ccc.and_then(|ccc| ccc.customer.as_ref()).and_then(|c| c.name.clone()).unwrap_or_default(),
+    ]
+}
+
+/// Duration as whole minutes and as decimal hours, which are easier to sum in spreadsheets than "7h 45m".
+fn duration_fields(duration: Option<TimeDelta>) -> Vec<String> {
+    match duration {
+        None    => vec![String::new(), String::new()],
+        Some(d) => vec![d.num_minutes().to_string(), format!("{:.2}", d.num_minutes() as f64 / 60.0)],
+    }
+}
+
+/// Writes punch lines given in ascending order. LOGIN and BREAK lines get the duration of the session they started.
+pub fn write_punches_csv(out: &mut dyn Write, punches: &[Punch], sessions: &[Session]) -> std::io::Result<usize> {
+    csv_line(out, &[
+        "timestamp", "type", "id", "description", "ccc_id", "ccc_code", "ccc_name", "project", "customer", "duration_min", "duration_h",
+    ].map(String::from))?;
+    for punch in punches {
+        let duration = sessions.iter()
+            .find(|s| s.punch.id == punch.id)
+            .map(|s| s.duration());
+        let mut fields = vec![
+            punch.timestamp.format(CSV_TIME_FORMAT).to_string(),
+            punch.typ.to_string(),
+            punch.id.to_string(),
+            punch.desc().to_string(),
+        ];
+        fields.extend(ccc_fields(punch));
+        fields.extend(duration_fields(duration));
+        csv_line(out, &fields)?;
+    }
+    Ok(punches.len())
+}
+
+pub fn write_sessions_csv(out: &mut dyn Write, sessions: &[Session]) -> std::io::Result<usize> {
+    csv_line(out, &[
+        "start", "end", "type", "id", "description", "ccc_id", "ccc_code", "ccc_name", "project", "customer", "duration_min", "duration_h",
+    ].map(String::from))?;
+    for session in sessions {
+        let mut fields = vec![
+            session.start.format(CSV_TIME_FORMAT).to_string(),
+            session.end.format(CSV_TIME_FORMAT).to_string(),
+            session.punch.typ.to_string(),
+            session.punch.id.to_string(),
+            session.punch.desc().to_string(),
+        ];
+        fields.extend(ccc_fields(&session.punch));
+        fields.extend(duration_fields(Some(session.duration())));
+        csv_line(out, &fields)?;
+    }
+    Ok(sessions.len())
+}
+
+
+// iCalendar (RFC 5545) lines end with CRLF and are folded to at most 75 octets.
+const ICS_LINE_MAX: usize = 75;
+const ICS_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Escapes text value, i.e backslashes, separators and line breaks.
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn ics_time(time: DateTime<FixedOffset>) -> String {
+    time.with_timezone(&Utc).format(ICS_TIME_FORMAT).to_string()
+}
+
+/// Writes content line, folding it without splitting multi-byte characters.
+fn ics_line(out: &mut dyn Write, line: &str) -> std::io::Result<()> {
+    let mut octets = 0;
+    for ch in line.chars() {
+        if octets + ch.len_utf8() > ICS_LINE_MAX {
+            write!(out, "\r\n ")?;
+            octets = 1;
+        }
+        write!(out, "{}", ch)?;
+        octets += ch.len_utf8();
+    }
+    write!(out, "\r\n")
+}
+
+/// Cost centre, project and customer of the punch line as event description.
+fn ics_description(punch: &Punch) -> String {
+    let ccc = punch.customer_costcentre.as_ref();
+    let mut lines = vec![format!("Cost centre: {} ({})", punch.ccc_name(), punch.ccc_id().map(|id| id.to_string()).unwrap_or_default())];
+    if let Some(project) = ccc.and_then(|ccc| ccc.project.as_ref()).and_then(|p| p.name.as_ref()) {
+        lines.push(format!("Project: {}", project));
+    }
+    if let Some(customer) = ccc.and_then(|ccc| ccc.customer.as_ref()).and_then(|c| c.name.as_ref()) {
+        lines.push(format!("Customer: {}", customer));
+    }
+    lines.push(format!("Punch ID: {}", punch.id));
+    lines.join("\n")
+}
+
+fn ics_event(out: &mut dyn Write, punch: &Punch, summary: &str, category: &str, start: DateTime<FixedOffset>, end: DateTime<FixedOffset>) -> std::io::Result<()> {
+    ics_line(out, "BEGIN:VEVENT")?;
+    ics_line(out, &format!("UID:kiho-punch-{}@{}", punch.id, crate::CONFIG_NAME))?;
+    ics_line(out, &format!("DTSTAMP:{}", Utc::now().format(ICS_TIME_FORMAT)))?;
+    ics_line(out, &format!("DTSTART:{}", ics_time(start)))?;
+    ics_line(out, &format!("DTEND:{}", ics_time(end)))?;
+    ics_line(out, &format!("SUMMARY:{}", ics_text(summary)))?;
+    ics_line(out, &format!("DESCRIPTION:{}", ics_text(&ics_description(punch))))?;
+    ics_line(out, &format!("CATEGORIES:{}", category))?;
+    ics_line(out, "TRANSP:TRANSPARENT")?;
+    ics_line(out, "END:VEVENT")
+}
+
+fn ics_calendar(out: &mut dyn Write, write_events: impl FnOnce(&mut dyn Write) -> std::io::Result<usize>) -> std::io::Result<usize> {
+    ics_line(out, "BEGIN:VCALENDAR")?;
+    ics_line(out, "VERSION:2.0")?;
+    ics_line(out, &format!("PRODID:-//{}//v{}//EN", APP_NAME, APP_VERSION))?;
+    ics_line(out, "CALSCALE:GREGORIAN")?;
+    let events = write_events(out)?;
+    ics_line(out, "END:VCALENDAR")?;
+    Ok(events)
+}
+
+/// Writes every punch line as a zero length event at its timestamp.
+pub fn write_punches_ics(out: &mut dyn Write, punches: &[Punch]) -> std::io::Result<usize> {
+    ics_calendar(out, |out| {
+        for punch in punches {
+            let summary = match punch.desc() {
+                ""   => punch.typ.to_string(),
+                desc => format!("{}: {}", punch.typ, desc),
+            };
+            ics_event(out, punch, &summary, &punch.typ.to_string(), punch.timestamp, punch.timestamp)?;
+        }
+        Ok(punches.len())
+    })
+}
+
+/// Writes every session as an event, summarized with the description of the punch line that started it.
+pub fn write_sessions_ics(out: &mut dyn Write, sessions: &[Session]) -> std::io::Result<usize> {
+    ics_calendar(out, |out| {
+        for session in sessions {
+            let (summary, category) = match session.kind {
+                SessionKind::Work  => (session.punch.desc().to_string(), "WORK"),
+                SessionKind::Break => (format!("Break: {}", session.punch.desc()), "BREAK"),
+            };
+            ics_event(out, &session.punch, &summary, category, session.start, session.end)?;
+        }
+        Ok(sessions.len())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::pair_sessions;
+
+    fn punch(id: u64, typ: &str, timestamp: &str, desc: &str) -> Punch {
+        serde_json::from_value(json!({
+            "id": id, "type": typ, "timestamp": timestamp, "description": desc,
+            "customerCostcentre": { "id": 101, "code": "R1", "name": "Rusting, \"the\" project" },
+        })).unwrap()
+    }
+
+    #[test]
+    fn csv_fields_quoted_when_needed() {
+        let punches = [
+            punch(1, "LOGIN",  "2024-09-02T08:00:00+03:00", "Review, \"quick\" one\nand more"),
+            punch(2, "LOGOUT", "2024-09-02T09:30:00+03:00", ""),
+        ];
+        let sessions = pair_sessions(&punches, DateTime::parse_from_rfc3339("2024-09-02T12:00:00+03:00").unwrap());
+        let mut out: Vec<u8> = Vec::new();
+        assert_eq!(write_punches_csv(&mut out, &punches, &sessions).unwrap(), 2);
+        assert_eq!(String::from_utf8(out).unwrap(), concat!(
+            "timestamp,type,id,description,ccc_id,ccc_code,ccc_name,project,customer,duration_min,duration_h\n",
+            "2024-09-02 08:00:00,LOGIN,1,\"Review, \"\"quick\"\" one\nand more\",101,R1,\"Rusting, \"\"the\"\" project\",,,90,1.50\n",
+            "2024-09-02 09:30:00,LOGOUT,2,,101,R1,\"Rusting, \"\"the\"\" project\",,,,\n",
+        ));
+        assert_eq!(csv_field("plain text"), "plain text");
+        assert_eq!(csv_field("carriage\rreturn"), "\"carriage\rreturn\"");
+    }
+
+    fn ics_output(line: &str) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        ics_line(&mut out, line).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn ics_lines_folded_at_75_octets() {
+        let ascii = format!("SUMMARY:{}", "x".repeat(100));
+        assert_eq!(ics_output(&ascii), format!("{}\r\n {}\r\n", &ascii[..75], &ascii[75..]));
+        assert_eq!(ics_output("END:VEVENT"), "END:VEVENT\r\n");
+
+        // Two octet characters never get split, so the first line is left an octet short
+        let multibyte = format!("SUMMARY:{}", "ä".repeat(40));
+        let folded = ics_output(&multibyte);
+        let lines: Vec<&str> = folded.strip_suffix("\r\n").unwrap().split("\r\n").collect();
+        assert_eq!(lines.iter().map(|l| l.len()).collect::<Vec<usize>>(), [74, 15]);
+        assert_eq!(folded.replace("\r\n ", "").trim_end(), multibyte);
+    }
+
+    #[test]
+    fn ics_text_escaped() {
+        assert_eq!(ics_text("Review; code, docs"), "Review\\; code\\, docs");
+        assert_eq!(ics_text("C:\\temp\r\nnext\nlast"), "C:\\\\temp\\nnext\\nlast");
+    }
+}
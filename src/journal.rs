@@ -0,0 +1,177 @@
+//
+// Local journal of every punch line created or fetched, keyed by punch ID.
+//
+// Journal is stored as JSON next to the configuration file. It makes working without Kiho API
+// possible (`--offline`) and keeps earlier versions of punch lines that were changed afterwards,
+// e.g edited in the Kiho web UI, as an audit trail.
+//
+
+use std::collections::BTreeMap;
+
+use chrono::prelude::*;
+
+use crate::punch::Punch;
+use crate::{profile_file_name, CONFIG_NAME};
+
+const JOURNAL_NAME: &str = "journal";
+
+// Latest punch lines are also stored separately, so that `status --cached` does not need to read
+// the whole journal, which grows forever.
+const RECENT_NAME:  &str = "recent";
+const RECENT_COUNT: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    /// Latest known version of the punch line.
+    pub punch:      Punch,
+    pub first_seen: DateTime<FixedOffset>,
+    pub last_seen:  DateTime<FixedOffset>,
+    /// Earlier versions of the punch line, oldest first, if it has been changed since first seen.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub earlier_versions: Vec<Punch>,
+    /// When the punch line was deleted using this application. Deleted punch lines are kept for the audit trail.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Journal {
+    pub punches: BTreeMap<u64, JournalEntry>,
+}
+impl Journal {
+    pub fn path() -> std::path::PathBuf {
+        confy::get_configuration_file_path(CONFIG_NAME, profile_file_name(JOURNAL_NAME).as_str())
+            .expect("Getting journal file path failed")
+            .with_extension("json")
+    }
+
+    fn recent_path() -> std::path::PathBuf {
+        confy::get_configuration_file_path(CONFIG_NAME, profile_file_name(RECENT_NAME).as_str())
+            .expect("Getting recent punches file path failed")
+            .with_extension("json")
+    }
+
+    /// Latest punch lines in ascending order, as they were when the journal was last stored.
+    pub fn load_recent() -> std::io::Result<Vec<Punch>> {
+        let path = Journal::recent_path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = std::fs::File::open(&path)?;
+        serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|err| std::io::Error::other(format!("Invalid recent punches '{}': {}", path.display(), err)))
+    }
+
+    pub fn load() -> std::io::Result<Journal> {
+        let path = Journal::path();
+        if !path.exists() {
+            return Ok(Journal::default());
+        }
+        let file = std::fs::File::open(&path)?;
+        serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|err| std::io::Error::other(format!("Invalid journal '{}': {}", path.display(), err)))
+    }
+
+    /// Stores the journal and the latest punch lines of it.
+    pub fn store(&self) -> std::io::Result<()> {
+        let mut recent = self.punches();
+        recent.sort_unstable_by_key(|pl| (pl.timestamp, pl.id));
+        let recent = &recent[recent.len().saturating_sub(RECENT_COUNT)..];
+        write_json(&Journal::path(), self)?;
+        write_json(&Journal::recent_path(), &recent)
+    }
+
+    /// Adds new punch lines and updates known ones. Returns number of new and changed punch lines.
+    pub fn record(&mut self, punches: &[Punch]) -> (usize, usize) {
+        let now = Local::now().fixed_offset();
+        let (mut new_cnt, mut changed_cnt) = (0, 0);
+        for punch in punches {
+            match self.punches.get_mut(&punch.id) {
+                None => {
+                    self.punches.insert(punch.id, JournalEntry {
+                        punch:      punch.clone(),
+                        first_seen: now,
+                        last_seen:  now,
+                        earlier_versions: Vec::new(),
+                        deleted:    None,
+                    });
+                    new_cnt += 1;
+                },
+                Some(entry) => {
+                    // Comparing as JSON b/c the API types do not implement `PartialEq`
+                    if serde_json::to_value(&entry.punch).ok() != serde_json::to_value(punch).ok() {
+                        let earlier = std::mem::replace(&mut entry.punch, punch.clone());
+                        entry.earlier_versions.push(earlier);
+                        changed_cnt += 1;
+                    }
+                    entry.last_seen = now;
+                },
+            }
+        }
+        (new_cnt, changed_cnt)
+    }
+
+    pub fn mark_deleted(&mut self, id: u64) {
+        if let Some(entry) = self.punches.get_mut(&id) {
+            entry.deleted = Some(Local::now().fixed_offset());
+        }
+    }
+
+    /// Latest versions of the punch lines that have not been deleted.
+    pub fn punches(&self) -> Vec<Punch> {
+        self.punches.values()
+            .filter(|entry| entry.deleted.is_none())
+            .map(|entry| entry.punch.clone())
+            .collect()
+    }
+}
+
+/// Writes JSON into a temporary file first, so that a failure never leaves the file half-written.
+fn write_json<T: serde::Serialize>(path: &std::path::Path, value: &T) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+    serde_json::to_writer(&mut writer, value)?;
+    std::io::Write::flush(&mut writer)?;
+    std::fs::rename(tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn punch(id: u64, desc: &str) -> Punch {
+        serde_json::from_value(json!({ "id": id, "type": "LOGIN", "timestamp": "2024-09-02T08:00:00+03:00", "description": desc })).unwrap()
+    }
+
+    #[test]
+    fn new_and_changed_punch_lines_counted() {
+        let mut journal = Journal::default();
+        assert_eq!(journal.record(&[punch(1, "Rusting"), punch(2, "Review")]), (2, 0));
+        assert_eq!(journal.record(&[punch(1, "Rusting"), punch(2, "Code review"), punch(3, "Docs")]), (1, 1));
+        assert_eq!(journal.record(&[punch(2, "Code review, again")]), (0, 1));
+
+        let entry = &journal.punches[&2];
+        assert_eq!(entry.punch.desc(), "Code review, again");
+        let earlier: Vec<&str> = entry.earlier_versions.iter().map(|pl| pl.desc()).collect();
+        assert_eq!(earlier, ["Review", "Code review"]);
+        assert!(journal.punches[&1].earlier_versions.is_empty());
+        assert!(entry.first_seen <= entry.last_seen);
+    }
+
+    #[test]
+    fn deleted_punch_lines_kept_but_hidden() {
+        let mut journal = Journal::default();
+        journal.record(&[punch(1, "Rusting"), punch(2, "Mistake")]);
+        journal.mark_deleted(2);
+        journal.mark_deleted(99);
+        let ids: Vec<u64> = journal.punches().iter().map(|pl| pl.id).collect();
+        assert_eq!(ids, [1]);
+        assert!(journal.punches[&2].deleted.is_some());
+        assert_eq!(journal.punches.len(), 2);
+    }
+}
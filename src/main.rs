@@ -26,12 +26,36 @@ use std::io::Write;
 
 use chrono::prelude::*;
 
+#[macro_use]
+mod output;
+use output::OutputFormat;
+mod error;
+use error::KihoError;
+mod export;
+use export::{ExportFormat, ExportWhat};
+mod punch;
+use punch::{KihoResponse, Punch, PunchType};
+mod client;
+use client::{HttpClient, KihoClient, MemoryClient, OfflineClient, PunchFilter};
+mod journal;
+use journal::Journal;
+mod period;
+use period::{parse_at_arg, Period, PeriodArgs};
+mod queue;
+use queue::PunchQueue;
+mod report;
+mod session;
+mod status;
+use status::{Status, StatusPreset};
+use session::{format_duration, pair_sessions, total_between, Session, SessionKind};
+
 // https://docs.rs/once_cell/latest/once_cell/
 use once_cell::sync::Lazy;
 static CLIARGS: Lazy<CliArgs> = Lazy::new(|| {
     let args = CliArgs::parse();
+    output::set_msg_target(msg_target(&args));
     if args.verbose > 0 {
-        println!("{} :: Parsing command line arguments (OnceCell/Lazy) done", Local::now().format(STAMP_FORMAT));
+        msgln!("{} :: Parsing command line arguments (OnceCell/Lazy) done", Local::now().format(STAMP_FORMAT));
     }
     args
 });
@@ -39,6 +63,7 @@ static CLIARGS: Lazy<CliArgs> = Lazy::new(|| {
 
 const APP_NAME:     &str = "Kiho Worktime Puncher";
 const CONFIG_NAME:  &str = "kiho-worktime-puncher";
+const STATE_NAME:   &str = "state";
 const APP_VERSION:  &str = env!("CARGO_PKG_VERSION");
 const STAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
@@ -46,16 +71,20 @@ const STAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 // Examples:
 //  https://v3.kiho.fi/api/v1/punch?mode=latest
 //  https://v3.kiho.fi/api/v1/punch?orderBy=timestamp+DESC&pageSize=10&type=LOGIN
+// Default production URL, can be changed using configuration, `--api-url` or `KIHO_API_URL` env variable.
 const KIHO_API_URL: &str = "https://v3.kiho.fi/api/v1/punch";
 
 // https://crates.io/crates/const_format/
 use const_format::concatcp;
 const USER_AGENT: &str = concatcp!(APP_NAME, " v", APP_VERSION);
 
+// Default `api_key` of a new configuration. Kiho API is never called with it.
+const API_KEY_PLACEHOLDER: &str = "Ask API Key from administrator";
+
 
 // https://docs.rs/crate/clap/latest
 // https://docs.rs/clap/latest/clap/_derive/_tutorial/index.html
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap::{ArgGroup, Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(about, version)]
@@ -72,6 +101,50 @@ struct CliArgs {
     /// Use `-vv` to get even more detailed output.
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
+    /// Named profile of the configuration to use, e.g for another Kiho tenant. (default: top level of configuration)
+    #[arg(short, long, env = "KIHO_PROFILE", value_name = "name")]
+    profile: Option<String>,
+    /// Kiho punch API URL, e.g staging tenant or local mock server. (default: from configuration)
+    #[arg(long, env = "KIHO_API_URL", value_name = "url")]
+    api_url: Option<String>,
+    /// Output format of punch lines and reports. With JSON formats only the results
+    /// are printed to stdout and everything else to stderr.
+    #[arg(short, long, value_enum, value_name = "format", default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+    /// Use punch lines stored in the local journal instead of Kiho API.
+    /// New punches are queued to be sent later with 'sync'. (default: false)
+    #[arg(long, default_value_t = false, conflicts_with = "fake_api")]
+    offline: bool,
+    /// Use in-memory fake Kiho API instead of the real one, e.g for testing commands.
+    /// Punch lines created with it are forgotten when the program exits.
+    #[arg(long, hide = true, default_value_t = false)]
+    fake_api: bool,
+}
+
+/// Command line options the commands depend on. Passed explicitly instead of reading `CLIARGS`,
+/// so that commands can be run without parsing the command line, e.g in tests.
+#[derive(Clone, Copy, Debug)]
+struct RunOpts {
+    dry_run: bool,
+    verbose: u8,
+    output:  OutputFormat,
+    /// Whether created and fetched punch lines are recorded into the local journal.
+    journal: bool,
+    /// Whether local state and offline queue files are used at all.
+    files:   bool,
+}
+impl RunOpts {
+    fn from_args(args: &CliArgs) -> RunOpts {
+        RunOpts {
+            dry_run: args.dry_run,
+            verbose: args.verbose,
+            output:  args.output,
+            // Fake punch lines never go to the journal and with `--offline` they came from it in the first place
+            journal: !args.fake_api && !args.offline,
+            // Fake punches must never mix with the real ones waiting in the offline queue
+            files:   !args.fake_api,
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -81,15 +154,40 @@ enum CliCommands {
         #[command(subcommand)]
         what: CliGetWhat,
     },
-    /// Add worktime break (NOT IMPLEMENTED)
-    Break,
+    /// Show what is being worked on right now and total worktime today
+    Status(StatusArgs),
+    /// Report worked hours per day and week, breaks subtracted. (default: this week)
+    Report(ReportArgs),
+    /// Export punch lines or sessions of a period into a file, e.g for spreadsheets or calendars. (default: this month)
+    Export(ExportArgs),
+    /// Add worktime break, e.g lunch or coffee break
+    Break(BreakDesc),
     /// Start working on something work related
-    Start(PunchDesc),
+    Start(StartArgs),
     /// Stop whatever worktime task was active
-    Stop,
+    Stop(StopArgs),
+    /// Stop the active task and start working on something else in one go
+    Switch(SwitchArgs),
+    /// Change description, cost centre or timestamp of an existing punch line
+    Edit(EditArgs),
+    /// Delete mistaken punch line
+    Delete(DeleteArgs),
+    /// Send punches queued while Kiho API was not reachable, in their original order
+    Sync(SyncArgs),
+}
+impl CliCommands {
+    /// Whether the command might call Kiho API. Commands showing only local things must work without API key.
+    fn uses_api(&self) -> bool {
+        match self {
+            CliCommands::Get { what } => matches!(what, CliGetWhat::Latest { .. } | CliGetWhat::Range { .. }),
+            CliCommands::Status(args) => !args.cached,
+            _                         => true,
+        }
+    }
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::upper_case_acronyms)]
 enum CliGetWhat {
     /// Get current loaded configuration
     Config,
@@ -97,8 +195,24 @@ enum CliGetWhat {
     CCC,
     /// Get list of configured 'recurring tasks'
     Tasks,
+    /// Get list of configured 'break types'
+    Breaks,
+    /// Get configured 'cost centre rules' in evaluation order
+    Rules,
+    /// Get names of configured profiles
+    Profiles,
     /// Print example login/logout JSONs
     JSON,
+    /// Get summary of the local punch journal, including punch lines changed in Kiho since first seen
+    Journal,
+    /// Get worktime punch lines of a period, e.g 'today', 'last-month' or '--from 2024-09-01 --to 2024-09-30'
+    Range {
+        #[command(flatten)]
+        period: PeriodArgs,
+        /// Punch type to get. (default: all types)
+        #[arg(long = "type", value_enum, value_name = "type")]
+        typ: Option<PunchType>,
+    },
     /// Get latest COUNT worktime BREAK/LOGIN/LOGOUT punch lines
     Latest {
         /// Number of punch lines to get
@@ -125,39 +239,168 @@ impl std::fmt::Display for PunchDesc {
     }
 }
 
-#[derive(ValueEnum, Clone, Copy)]
-enum PunchType {
-    BREAK,
-    LOGIN,
-    LOGOUT,
+#[derive(Args)]
+struct StartArgs {
+    #[command(flatten)]
+    punch: PunchDesc,
+    /// Customer cost centre ID or name. Asked from the configured ones if not given.
+    #[arg(long, value_name = "id|name")]
+    ccc: Option<String>,
+    /// Skip checking that the latest punch is not already a LOGIN. (default: false)
+    #[arg(short, long, default_value_t = false)]
+    force: bool,
+    /// Make LOGOUT punch first if some task is still active. (default: false)
+    #[arg(short, long, default_value_t = false, conflicts_with = "force")]
+    auto_logout: bool,
+    /// Punch retroactively at the given time, e.g '08:15' or 'yesterday 17:30'. (default: now)
+    #[arg(long, value_name = "time", value_parser = parse_at_arg)]
+    at: Option<DateTime<FixedOffset>>,
 }
-impl std::fmt::Display for PunchType {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            PunchType::BREAK => write!(f, "BREAK"),
-            PunchType::LOGIN => write!(f, "LOGIN"),
-            PunchType::LOGOUT => write!(f, "LOGOUT"),
-        }
+
+#[derive(Args)]
+struct SwitchArgs {
+    #[command(flatten)]
+    punch: PunchDesc,
+    /// Customer cost centre ID or name. Asked from the configured ones if not given.
+    #[arg(long, value_name = "id|name")]
+    ccc: Option<String>,
+    /// Make LOGOUT punch without checking that some task is active. (default: false)
+    #[arg(short, long, default_value_t = false)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct StatusArgs {
+    /// Use the latest punch lines stored locally instead of Kiho API, e.g for shell prompts. (default: false)
+    #[arg(short, long, default_value_t = false)]
+    cached: bool,
+    /// Print only the status formatted with template having placeholders
+    /// {state}, {desc}, {ccc}, {ccc_id}, {since}, {elapsed} and {today}, e.g '{state}: {desc} {elapsed}'
+    #[arg(long, value_name = "template", conflicts_with = "preset")]
+    format: Option<String>,
+    /// Print only the status in a ready-made format for status bars
+    #[arg(long, value_enum, value_name = "preset")]
+    preset: Option<StatusPreset>,
+}
+impl StatusArgs {
+    fn formatted(&self) -> bool {
+        self.format.is_some() || self.preset.is_some()
     }
 }
 
+#[derive(Args)]
+struct ReportArgs {
+    #[command(flatten)]
+    period: PeriodArgs,
+    /// Report worked hours per customer cost centre instead of per day. (default: false)
+    #[arg(long, default_value_t = false)]
+    by_ccc: bool,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    /// File format to export
+    #[arg(value_enum, value_name = "format")]
+    format: ExportFormat,
+    /// Export raw punch lines or paired sessions
+    #[arg(value_enum, value_name = "what")]
+    what: ExportWhat,
+    #[command(flatten)]
+    period: PeriodArgs,
+    /// File to write, '-' for standard output. (default: e.g 'kiho-sessions-2024-09-01-2024-09-30.csv')
+    #[arg(long, value_name = "path")]
+    file: Option<String>,
+}
+
+#[derive(Args)]
+struct StopArgs {
+    /// Skip checking that the latest punch is not already a LOGOUT. (default: false)
+    #[arg(short, long, default_value_t = false)]
+    force: bool,
+    /// Punch retroactively at the given time, e.g '17:30' or 'yesterday 17:30'. (default: now)
+    #[arg(long, value_name = "time", value_parser = parse_at_arg)]
+    at: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("changes").required(true).multiple(true)))]
+struct EditArgs {
+    /// ID of the punch line, as shown in the 'Punch ID' column
+    #[arg(value_name = "punch-id")]
+    id: u64,
+    /// New description
+    #[arg(long, value_name = "description", group = "changes")]
+    desc: Option<String>,
+    /// New customer cost centre ID or name
+    #[arg(long, value_name = "id|name", group = "changes")]
+    ccc: Option<String>,
+    /// New timestamp, e.g '08:15' or 'yesterday 17:30'
+    #[arg(long, value_name = "time", value_parser = parse_at_arg, group = "changes")]
+    at: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Args)]
+struct SyncArgs {
+    /// Drop the first queued punch without sending it, e.g when Kiho keeps refusing it. (default: false)
+    #[arg(long, default_value_t = false)]
+    drop_first: bool,
+}
 
+#[derive(Args)]
+struct DeleteArgs {
+    /// ID of the punch line, as shown in the 'Punch ID' column
+    #[arg(value_name = "punch-id")]
+    id: u64,
+    /// Delete without asking for confirmation. (default: false)
+    #[arg(short, long, default_value_t = false)]
+    yes: bool,
+}
+
+#[derive(Args, Clone)]
+struct BreakDesc {
+    /// Break type, e.g 'lunch'. Asked from the configured ones if not given.
+    #[arg(value_name = "type")]
+    typ: Option<String>,
+}
+
+// NOTE: `serde(default)` fills fields missing from older configuration files using `Default` below
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 struct KihoWtConfig {
     title:   String,
     api_key: String,
+    // Command printing the API key on its first line, e.g `pass show kiho`. Used instead of `api_key`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key_cmd: Option<String>,
+    // File containing only the API key. Used instead of `api_key`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key_file: Option<String>,
+    // Set when the selected profile has its own API key source, which then wins over `KIHO_API_KEY`
+    #[serde(skip)]
+    profile_api_key: bool,
+    api_url: String,
     updated: String,
     // NOTE
     // - Putting `recurring_tasks` after `cost_centres` result in `SerializeTomlError(ValueAfterTable)` error :/
     // - HashMap KEY has to be also `String` b/c TOML keys are always interpreted as strings (i.e cannot be `&str`).
     recurring_tasks: Vec<String>,
+    break_types: Vec<String>,
     cost_centres: std::collections::HashMap<String,String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    ccc_rules: Vec<CccRule>,
+    // NOTE: Has to be the last one for the same `ValueAfterTable` reason as above
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    profiles: std::collections::BTreeMap<String, KihoWtProfile>,
 }
 impl Default for KihoWtConfig {
     fn default() -> Self {
         KihoWtConfig {
             title:   format!("Configuration file for '{}'", APP_NAME),
-            api_key: "Ask API Key from administrator".to_string(),
+            api_key: API_KEY_PLACEHOLDER.to_string(),
+            api_key_cmd:  None,
+            api_key_file: None,
+            profile_api_key: false,
+            api_url: KIHO_API_URL.to_string(),
             updated: Local::now().format("%d.%m.%Y").to_string(),
             cost_centres: std::collections::HashMap::from([
                 (String::from("000000"), String::from("Dummy example cost centre")),
@@ -165,66 +408,299 @@ impl Default for KihoWtConfig {
             recurring_tasks: vec![
                 String::from("Dummy example recurring task description"),
             ],
+            break_types: vec![
+                String::from("Lunch"),
+                String::from("Coffee"),
+                String::from("Personal"),
+            ],
+            ccc_rules: Vec::new(),
+            profiles: std::collections::BTreeMap::new(),
         }
     }
 }
+impl KihoWtConfig {
+    /// Returns configuration with the fields set in the named profile replacing the top level ones.
+    fn with_profile(mut self, name: &str) -> Option<KihoWtConfig> {
+        let profile = self.profiles.get(name)?.clone();
+        // API key sources are replaced all together, so that e.g top level `api_key_cmd` cannot shadow profile's `api_key`
+        if profile.api_key.is_some() || profile.api_key_cmd.is_some() || profile.api_key_file.is_some() {
+            self.api_key      = profile.api_key.unwrap_or_default();
+            self.api_key_cmd  = profile.api_key_cmd;
+            self.api_key_file = profile.api_key_file;
+            self.profile_api_key = true;
+        }
+        if let Some(api_url) = profile.api_url { self.api_url = api_url; }
+        if let Some(tasks)   = profile.recurring_tasks { self.recurring_tasks = tasks; }
+        if let Some(breaks)  = profile.break_types { self.break_types = breaks; }
+        if let Some(cccs)    = profile.cost_centres { self.cost_centres = cccs; }
+        if let Some(rules)   = profile.ccc_rules { self.ccc_rules = rules; }
+        Some(self)
+    }
+
+    /// Compiles regex patterns of the cost centre rules once, so that a bad one is found before any punches.
+    fn compiled(mut self) -> Result<KihoWtConfig, KihoError> {
+        for rule in self.ccc_rules.iter_mut().filter(|rule| rule.regex) {
+            let compiled = regex::Regex::new(&rule.pattern).map_err(|err| {
+                KihoError::Config(format!("Invalid cost centre rule regex '{}': {}", rule.pattern, err))
+            })?;
+            rule.compiled = Some(compiled);
+        }
+        Ok(self)
+    }
+}
 
-fn load_config() -> KihoWtConfig {
+// Named profile, e.g `[profiles.client]` for a client's Kiho tenant. Fields left out are taken from the
+// top level of the configuration, which is also used as is when no profile is selected.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct KihoWtProfile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key_cmd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recurring_tasks: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    break_types: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cost_centres: Option<std::collections::HashMap<String,String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ccc_rules: Option<Vec<CccRule>>,
+}
+
+// Maps punch descriptions to cost centres. Rules are evaluated in the configured order and the first
+// matching one wins. Patterns are plain case sensitive substrings unless `regex = true` is set,
+// in which case e.g `(?i)` can be used to make the match case insensitive.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CccRule {
+    pattern: String,
+    #[serde(default)]
+    regex: bool,
+    ccc_id: u32,
+    /// Compiled `pattern` when `regex = true`, see `KihoWtConfig::compiled`.
+    #[serde(skip)]
+    compiled: Option<regex::Regex>,
+}
+impl CccRule {
+    fn matches(&self, desc: &str) -> bool {
+        match (self.regex, &self.compiled) {
+            (false, _)       => desc.contains(&self.pattern),
+            (true, Some(re)) => re.is_match(desc),
+            (true, None)     => unreachable!("Cost centre rule regex '{}' used without compiling it", self.pattern),
+        }
+    }
+}
+
+/// Returns cost centre ID of the first rule matching the given punch description.
+fn match_costcentre_rule(rules: &[CccRule], desc: &str, opts: &RunOpts) -> Option<u32> {
+    let rule = rules.iter().find(|rule| rule.matches(desc))?;
+    if opts.verbose > 0 {
+        msgln!("{} :: Cost centre rule '{}' matched the description", Local::now().format(STAMP_FORMAT), rule.pattern);
+    }
+    Some(rule.ccc_id)
+}
+
+
+// Things remembered between runs, e.g last selected cost centre.
+// Stored next to the configuration file so that user's own config is never rewritten.
+// Like the journal and the offline queue, kept separately for each profile.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct KihoWtState {
+    last_ccc_id: Option<u32>,
+}
+
+fn load_state(opts: &RunOpts) -> KihoWtState {
+    if !opts.files {
+        return KihoWtState::default();
+    }
+    confy::load(CONFIG_NAME, profile_file_name(STATE_NAME).as_str()).unwrap_or_else(|err| {
+        msgln!("WARNING: Loading saved state failed, using defaults: {:?}", err);
+        KihoWtState::default()
+    })
+}
+
+fn store_state(state: &KihoWtState, opts: &RunOpts) {
+    if opts.dry_run || !opts.files {
+        return;
+    }
+    if let Err(err) = confy::store(CONFIG_NAME, profile_file_name(STATE_NAME).as_str(), state) {
+        msgln!("WARNING: Saving state failed: {:?}", err);
+    }
+}
+
+fn load_config() -> Result<KihoWtConfig, KihoError> {
     let cfg_name = CONFIG_NAME;
     let cfg_path = confy::get_configuration_file_path(cfg_name, None)
-        .expect("Getting confy configuration file path failed");
+        .map_err(|err| KihoError::Config(format!("Getting configuration file path failed: {}", err)))?;
     if CLIARGS.verbose > 0 {
-        println!("{} :: Loading configuration from '{}'", Local::now().format(STAMP_FORMAT), cfg_path.display());
+        msgln!("{} :: Loading configuration from '{}'", Local::now().format(STAMP_FORMAT), cfg_path.display());
+    }
+    let cfg: KihoWtConfig = confy::load(cfg_name, None)
+        .map_err(|err| KihoError::Config(format!("Loading configuration from '{}' failed: {}", cfg_path.display(), err)))?;
+    let Some(profile) = &CLIARGS.profile else {
+        return cfg.compiled();
+    };
+    if CLIARGS.verbose > 0 {
+        msgln!("{} :: Using configuration profile '{}'", Local::now().format(STAMP_FORMAT), profile);
+    }
+    let profiles: Vec<String> = cfg.profiles.keys().cloned().collect();
+    let cfg = cfg.with_profile(profile).ok_or_else(|| match profiles.is_empty() {
+        true  => KihoError::Config(format!("Profile '{}' not found, no profiles in '{}'", profile, cfg_path.display())),
+        false => KihoError::Config(format!("Profile '{}' not found in '{}', available profiles: {}", profile, cfg_path.display(), profiles.join(", "))),
+    })?;
+    let _ = PROFILE.set(profile.clone());
+    cfg.compiled()
+}
+
+// Name of the selected profile, set once in `load_config`.
+static PROFILE: once_cell::sync::OnceCell<String> = once_cell::sync::OnceCell::new();
+
+/// Name of a file stored next to the configuration, e.g "journal", suffixed with the selected profile
+/// so that punch lines and queued punches of different Kiho tenants never get mixed up.
+pub fn profile_file_name(name: &str) -> String {
+    match PROFILE.get() {
+        None          => name.to_string(),
+        Some(profile) => format!("{}-{}", name, profile),
     }
-    let cfg: KihoWtConfig = confy::load(cfg_name, None).unwrap_or_else(|err| {
-        println!("ERROR: {:?}", err);
-        panic!("Loading configuration from '{}' failed!", cfg_path.display());
-    });
-    cfg
 }
 
 
-// TODO [#11]: List 'cost_centres' from the configuration and ask user
-// fn ask_costcentre(costcentres: std::collections::HashMap<String,String>) -> u32 {
-fn ask_costcentre(desc: &str) -> u32 {
-    match desc.contains("ISO27") {
-        true => 892621u32,  // 'ISO27001 2024'
-        _    => 901184u32,  // 'Tuotekehitys Yleinen'
+/// Returns configured cost centres as `(id, name)` pairs sorted by name.
+fn sorted_costcentres(costcentres: &std::collections::HashMap<String,String>) -> Result<Vec<(u32, &str)>, KihoError> {
+    let mut cccs: Vec<(u32, &str)> = costcentres.iter()
+        .map(|(id, name)| match id.parse::<u32>() {
+            Ok(id) => Ok((id, name.as_str())),
+            Err(_) => Err(KihoError::Config(format!("Cost centre ID '{}' in the configuration is not a number", id))),
+        })
+        .collect::<Result<_, _>>()?;
+    cccs.sort_unstable_by(|(id1, name1), (id2, name2)| name1.cmp(name2).then(id1.cmp(id2)));
+    Ok(cccs)
+}
+
+/// Finds cost centre by its ID or (case insensitive) name from the configured ones.
+/// Unconfigured numeric IDs are accepted as is, because Kiho has many more cost centres than anyone configures.
+fn find_costcentre(costcentres: &std::collections::HashMap<String,String>, id_or_name: &str) -> Result<u32, KihoError> {
+    let cccs = sorted_costcentres(costcentres)?;
+    let wanted = id_or_name.trim();
+    let found = cccs.iter()
+        .find(|(id, _)| id.to_string() == wanted)
+        .or_else(|| cccs.iter().find(|(_, name)| name.eq_ignore_ascii_case(wanted)));
+    match found {
+        Some((id, _)) => Ok(*id),
+        None => match wanted.parse::<u32>() {
+            Ok(id) => {
+                msgln!("NOTE: Cost centre ID {} not found from the configuration, using it anyway", id);
+                Ok(id)
+            },
+            Err(_) => Err(KihoError::Config(format!("Cost centre '{}' not found from the configuration", wanted))),
+        },
     }
 }
 
+fn ask_costcentre(costcentres: &std::collections::HashMap<String,String>, last_id: Option<u32>) -> Result<u32, KihoError> {
+    if costcentres.is_empty() {
+        return Err(KihoError::Config(String::from("No 'cost_centres' found from the configuration")));
+    }
+    let cccs = sorted_costcentres(costcentres)?;
+    let choices: Vec<String> = cccs.iter()
+        .map(|(id, name)| format!("{} ({})", name, id))
+        .collect();
+    let default = last_id.and_then(|last| cccs.iter().position(|(id, _)| *id == last));
+    msgln!("{} :: Please select customer cost centre:", Local::now().format(STAMP_FORMAT));
+    let idx = ask_list_choice(&choices, "Which cost centre you want to use", default);
+    Ok(cccs[idx].0)
+}
+
 fn ask_recurring_desc(tasks: Vec<String>) -> PunchDesc {
-    let tasks_cnt = tasks.len();
-    println!("{} :: No punch description given.\nPlease select one from the available recurring ones:", Local::now().format(STAMP_FORMAT));
-    for idx in 0..tasks_cnt {
-        println!("{:>4}: {}", (idx+1), tasks[idx]);
+    msgln!("{} :: No punch description given.\nPlease select one from the available recurring ones:", Local::now().format(STAMP_FORMAT));
+    let idx = ask_list_choice(&tasks, "Which task you want to start", None);
+    PunchDesc { desc: Some(tasks[idx].clone()) }
+}
+
+fn ask_break_type(break_types: Vec<String>) -> Result<PunchDesc, KihoError> {
+    if break_types.is_empty() {
+        return Err(KihoError::Config(String::from("No 'break_types' found from the configuration")));
+    }
+    msgln!("{} :: No break type given.\nPlease select one from the available ones:", Local::now().format(STAMP_FORMAT));
+    let idx = ask_list_choice(&break_types, "Which break you want to take", None);
+    Ok(PunchDesc { desc: Some(break_types[idx].clone()) })
+}
+
+/// Prints numbered list of `choices` and keeps asking `question` until user selects one of them.
+/// Returns zero based index of the selected choice, or `default` if user just presses enter.
+/// Exits the whole program if user chooses to cancel.
+fn ask_list_choice(choices: &[String], question: &str, default: Option<usize>) -> usize {
+    let choices_cnt = choices.len();
+    for (idx, choice) in choices.iter().enumerate() {
+        let marker = if default == Some(idx) { "*" } else { " " };
+        msgln!("{:>4}:{}{}", (idx+1), marker, choice);
     }
+    let default_hint = match default {
+        Some(idx) => format!(", enter for {}", idx+1),
+        None      => String::new(),
+    };
 
     let mut user_choice = String::new();
-    let description: &String = loop {
+    loop {
         user_choice.clear();
-        print!("Which task you want to start [1-{tasks_cnt}, or (c)ancel]? ");
+        msg!("{question} [1-{choices_cnt}{default_hint}, or (c)ancel]? ");
         io::stdout().flush().unwrap();
-        std::io::stdin().read_line(&mut user_choice)
+        let read_cnt = std::io::stdin().read_line(&mut user_choice)
             .expect("Error reading user's choice");
         user_choice = user_choice.trim().to_lowercase();
-        if user_choice == "c" {
-            println!("EXITING...");
+        // End of input, e.g when run from a script, is handled like cancel instead of asking forever
+        if user_choice == "c" || read_cnt == 0 {
+            msgln!("EXITING...");
             std::process::exit(0);
         }
+        if user_choice.is_empty() {
+            if let Some(idx) = default {
+                break idx;
+            }
+        }
         match user_choice.parse::<usize>() {
-            Ok(idx) if idx > 0 && idx <= tasks_cnt => break &tasks[idx-1],
-            _                                      => continue,
+            Ok(idx) if idx > 0 && idx <= choices_cnt => break idx-1,
+            _                                        => continue,
         };
-    };
-    PunchDesc { desc: Some(String::from(description)) }
+    }
+}
+
+
+/// Asks yes/no `question`, where anything else than yes means no, end of input included.
+fn ask_confirmation(question: &str) -> bool {
+    msg!("{question} [y/N]? ");
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)
+        .expect("Error reading user's answer");
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
 }
 
 
-fn create_punch_json(punch_type: PunchType, punch_desc: Option<PunchDesc>, ccc_id: Option<u32>) -> serde_json::Value {
-    let timestamp: String = Local::now().format("%Y-%m-%dT%H:%M:%S%Z").to_string();
+/// Creates JSON body for a new punch line. Retroactive punches get the requested time `at` as
+/// their timestamp, while the real timestamp always tells when the punch was actually made.
+fn create_punch_json(punch_type: PunchType, punch_desc: Option<PunchDesc>, ccc_id: Option<u32>, at: Option<DateTime<FixedOffset>>, opts: &RunOpts) -> serde_json::Value {
+    let real_timestamp: String = Local::now().format("%Y-%m-%dT%H:%M:%S%Z").to_string();
+    let timestamp: String = match at {
+        Some(at) => at.format("%Y-%m-%dT%H:%M:%S%Z").to_string(),
+        None     => real_timestamp.clone(),
+    };
     let json = match punch_type {
-        PunchType::BREAK => panic!("Starting a BREAK not supported!"),
+        PunchType::BREAK => {
+            json!({
+                "newPunch": {
+                    "type": punch_type.to_string(),
+                    "description": punch_desc.expect("JSON ERROR: Break punch has to have 'Description'").to_string(),
+                    "timestamp": timestamp,
+                    "realTimestamp": real_timestamp
+                }
+            })
+        },
         PunchType::LOGIN => {
             json!({
                 "newPunch": {
@@ -232,7 +708,7 @@ fn create_punch_json(punch_type: PunchType, punch_desc: Option<PunchDesc>, ccc_i
                     "description": punch_desc.expect("JSON ERROR: Start punch has to have 'Description'").to_string(),
                     "customerCostcentre": { "id": ccc_id.expect("JSON ERROR: Start punch has to have 'CustomerCostCentre' ID") },
                     "timestamp": timestamp,
-                    "realTimestamp": timestamp
+                    "realTimestamp": real_timestamp
                 }
             })
         },
@@ -241,13 +717,33 @@ fn create_punch_json(punch_type: PunchType, punch_desc: Option<PunchDesc>, ccc_i
                 "newPunch": {
                     "type": punch_type.to_string(),
                     "timestamp": timestamp,
-                    "realTimestamp": timestamp
+                    "realTimestamp": real_timestamp
                 }
             })
         },
+        PunchType::UNKNOWN => panic!("Creating UNKNOWN punch type not supported!"),
     };
-    if CLIARGS.verbose > 0 {
-        println!("CREATED PUNCH JSON:\n{:#}", json); // Using `:#` gives pretty-formated JSON output
+    if opts.verbose > 0 {
+        msgln!("CREATED PUNCH JSON:\n{:#}", json); // Using `:#` gives pretty-formated JSON output
+    }
+    json
+}
+
+/// Creates JSON body for changing an existing punch line. Only the given fields are changed.
+fn create_edit_json(punch_desc: Option<&str>, ccc_id: Option<u32>, at: Option<DateTime<FixedOffset>>, opts: &RunOpts) -> serde_json::Value {
+    let mut changes = serde_json::Map::new();
+    if let Some(desc) = punch_desc {
+        changes.insert(String::from("description"), json!(desc));
+    }
+    if let Some(id) = ccc_id {
+        changes.insert(String::from("customerCostcentre"), json!({ "id": id }));
+    }
+    if let Some(at) = at {
+        changes.insert(String::from("timestamp"), json!(at.format("%Y-%m-%dT%H:%M:%S%Z").to_string()));
+    }
+    let json = json!({ "punch": changes });
+    if opts.verbose > 0 {
+        msgln!("CREATED EDIT JSON:\n{:#}", json);
     }
     json
 }
@@ -275,234 +771,1068 @@ fn print_example_jsons() {
     println!("EXAMPLE JSON LOGIN/LOGOUT RESPONSES");
     let json_punch_login_resp  = json!({"result":{"address":null,"checkEventId":null,"customerCostcentre":{"code":9006,"costcenter":{"code":"21","deleted":false,"description":"Kiho AI Business Platform","id":30654,"name":"Palvelinympäristön kehitys","vismaCode":""},"customer":{"code":7001,"id":4410,"identity":"1862344-1","name":"Kiho Oy","nameExtra":"","nickname":""},"deleted":true,"description":null,"favourited":0,"id":101124,"name":"Palvelinympäristön kehitys","project":{"active":false,"code":"272","deleted":false,"description":"272/31/2019 / Tekes","id":109,"name":"Kiho AI Business Platform"},"workOrderNumber":null,"worksite":null},"description":"Rusting it out","device_sn":"","id":13586650,"labels":[],"location":null,"locationValidationEvent":null,"realTimestamp":"2023-08-24T08:02:12+03:00","source":"UNKNOWN","timestamp":"2023-08-24T08:02:12+03:00","type":"LOGIN","user":{"id":27874,"name":"Lång Jani","personNumber":"","teams":[{"id":4442,"isDefaultTeam":true,"name":"Team Sysadmin"}]},"wagecode":{"code":"0001","id":1268,"name":"Kuukausipalkka","type":"WORK"},"worklabel":null}});
     let json_punch_logout_resp = json!({"result": {"address": null,"checkEventId": null,"customerCostcentre": null,"description": "","device_sn": "","id": 13587416, "labels": [], "location": null, "locationValidationEvent": null, "realTimestamp": "2023-08-24T09:44:40+03:00", "source": "UNKNOWN", "timestamp": "2023-08-24T09:44:40+03:00", "type": "LOGOUT", "user": {"id": 27874,"name": "Lång Jani","personNumber": "", "teams": [{"id": 4442, "isDefaultTeam": true, "name": "Team Sysadmin" }]}, "wagecode": null, "worklabel": null}});
-    for json_resp in &[json_punch_login_resp, json_punch_logout_resp] {
-        let punch_desc = &json_resp["result"]["description"].as_str().unwrap();
-        let punch_id   = &json_resp["result"]["id"];
-        let punch_time = &json_resp["result"]["timestamp"].as_str().unwrap();
-        let punch_type = &json_resp["result"]["type"].as_str().unwrap();
-        println!("{punch_time} {punch_type} '{punch_desc}' (id: {punch_id})");
-        let ccc = &json_resp["result"]["customerCostcentre"];
-        println!("'CustomerCostcentre'\n{:#}", ccc);
+    for json_resp in [json_punch_login_resp, json_punch_logout_resp] {
+        let punch = serde_json::from_value::<KihoResponse<Punch>>(json_resp)
+            .expect("Example JSON does not match the punch model")
+            .result;
+        println!("{} {} '{}' (id: {})", punch.timestamp.to_rfc3339(), punch.typ, punch.desc(), punch.id);
+        println!("'CustomerCostcentre'\n{}", serde_json::to_string_pretty(&punch.customer_costcentre).unwrap());
     }
 }
 
 
-fn print_punch_lines_asc(plines: &Vec<serde_json::Value>) {
-    // Using `pl.get("description")` instead of `pl["description"]` is more idiomatic
-    // when dealing with `Option` values. Furthermore it does not blow up on your face.
+/// Prints punch lines in ascending order either as a table or as JSON, depending on `--output`.
+fn print_punch_list(plines: &[Punch], opts: &RunOpts) {
+    if opts.output != OutputFormat::Table {
+        let mut ascending = plines.to_vec();
+        ascending.sort_unstable_by_key(|pl| (pl.timestamp, pl.id));
+        output::print_records(opts.output, &ascending);
+        return;
+    }
+    if plines.is_empty() {
+        println!("NONE FOUND!");
+        return;
+    }
+    print_punch_lines_asc(plines);
+}
+
+fn print_punch_lines_asc(plines: &[Punch]) {
     let desc_width = plines.iter()
-        .filter_map(|pl| pl.get("description")
-            .and_then(|desc| desc.as_str())
-            .and_then(|desc| Some(desc.len()))
-        )
+        .map(|pl| pl.desc().len())
         .max()
         .unwrap_or(40);
 
     // Using 'unstable' sort is normally faster than normal 'stable' sort
     // - https://doc.rust-lang.org/std/primitive.slice.html#method.sort_unstable_by
-    let mut ascending = plines.clone();
-    ascending.sort_unstable_by(|pl1, pl2| {
-        let s1 = pl1.get("timestamp")
-            .and_then(|stamp| stamp.as_str())
-            .and_then(|stamp| DateTime::parse_from_rfc3339(stamp).ok());
-        let s2 = pl2.get("timestamp")
-            .and_then(|stamp| stamp.as_str())
-            .and_then(|stamp| DateTime::parse_from_rfc3339(stamp).ok());
-        s1.cmp(&s2)
-    });
+    let mut ascending: Vec<&Punch> = plines.iter().collect();
+    ascending.sort_unstable_by_key(|pl| (pl.timestamp, pl.id));
 
     // https://doc.rust-lang.org/rust-by-example/hello/print.html
     println!("| {: <19} | {: <6} | {: <8} | {: <20} | {: <desc_width$} |", "Punch Timestamp", "Type", "Punch ID", "Cost Centre Name", "Punch Description");
     println!("|-{:-<19}-|-{:-<6}-|-{:-<8}-|-{:-<20}-|-{:-<desc_width$}-|", "", "", "", "", "");
     ascending.into_iter().for_each(|pl|
-        print_punch_line(&pl, Some(desc_width))
+        print_punch_line(pl, Some(desc_width))
     );
     println!("|-{:-<19}-|-{:-<6}-|-{:-<8}-|-{:-<20}-|-{:-<desc_width$}-|", "", "", "", "", "");
 }
 
-fn print_punch_line(pl: &serde_json::Value, desc_col_width: Option<usize>) {
-    let punch_id   = &pl["id"];
-    let punch_desc = &pl["description"].as_str().unwrap_or_else(|| "");
-    let punch_time = &pl["timestamp"].as_str().unwrap_or_else(||   "");
-    let punch_type = &pl["type"].as_str().unwrap_or_else(||        "");
-    let ccc_name   = &pl["customerCostcentre"]["name"]
-        .as_str().unwrap_or_else(|| "");
+fn print_punch_line(pl: &Punch, desc_col_width: Option<usize>) {
+    let punch_desc = pl.desc();
     let desc_width = match desc_col_width {
         None    => punch_desc.len(),
         Some(w) => w,
     };
 
-    // Parse "2024-09-04T15:39:37+03:00" into normal "dd.mm.yyyy HH:MM:SS" format:
-    let chrono_dt: DateTime<FixedOffset> = punch_time.parse().unwrap();
-    let normal_dt = chrono_dt.format("%d.%m.%Y %H:%M:%S");
+    // Show "2024-09-04T15:39:37+03:00" in normal "dd.mm.yyyy HH:MM:SS" format:
+    let normal_dt = pl.timestamp.format("%d.%m.%Y %H:%M:%S");
 
-    println!("| {: <19} | {: <6} | {: <8} | {: <20} | {: <desc_width$} |", normal_dt, punch_type, punch_id, ccc_name, punch_desc);
+    println!("| {: <19} | {: <6} | {: <8} | {: <20} | {: <desc_width$} |", normal_dt, pl.typ, pl.id, pl.ccc_name(), punch_desc);
 }
 
 
-fn get_latest_punch(api_key: String, punch_type: Option<PunchType>, punch_count: u32) {
-    println!("{} :: Starting HTTP GET request...", Local::now().format(STAMP_FORMAT));
-    let mut params = vec![
-        // ("mode",  String::from("latest")),           // Returns SINGLE `result` object instead of an ARRAY :/
-        ("orderBy",  String::from("timestamp DESC")),   // NOTE: Nowadays `+` means SPACE in URLs like `%20` used to be !
-        ("pageSize", punch_count.to_string()),
-    ];
+fn get_latest_punch(client: &dyn KihoClient, punch_type: Option<PunchType>, punch_count: u32, opts: &RunOpts) -> Result<(), KihoError> {
     let punch_list_header = match punch_type {
-        None     => {
-            format!("Latest {} worktime punch line(s) in ascending order", punch_count)
+        None     => format!("Latest {} worktime punch line(s) in ascending order", punch_count),
+        Some(pt) => format!("Latest {} worktime {} punch line(s) in ascending order", punch_count, pt),
+    };
+    let Some(punch_lines) = fetch_latest_punches(client, punch_type, punch_count, opts)? else {
+        return Ok(());
+    };
+    msgln!("{} :: {}:", Local::now().format(STAMP_FORMAT), punch_list_header);
+    print_punch_list(&punch_lines, opts);
+    Ok(())
+}
+
+fn get_punch_range(client: &dyn KihoClient, period: &PeriodArgs, punch_type: Option<PunchType>, opts: &RunOpts) -> Result<(), KihoError> {
+    let (from, to) = period.resolve(Local::now(), Period::Today);
+    let filter = PunchFilter { punch_type, from: Some(from), to: Some(to) };
+    let Some(punch_lines) = fetch_punches(client, filter, None, opts)? else {
+        return Ok(());
+    };
+    let type_str = punch_type.map(|pt| format!(" {}", pt)).unwrap_or_default();
+    msgln!("{} :: Worktime{} punch line(s) from {} to {} in ascending order:", Local::now().format(STAMP_FORMAT),
+        type_str, from.format("%d.%m.%Y %H:%M"), to.format("%d.%m.%Y %H:%M"));
+    print_punch_list(&punch_lines, opts);
+    Ok(())
+}
+
+/// Fetches latest `punch_count` punch lines in descending timestamp order.
+/// Returns `None` when running in dry-run mode.
+fn fetch_latest_punches(client: &dyn KihoClient, punch_type: Option<PunchType>, punch_count: u32, opts: &RunOpts) -> Result<Option<Vec<Punch>>, KihoError> {
+    let filter = PunchFilter { punch_type, ..Default::default() };
+    fetch_punches(client, filter, Some(punch_count), opts)
+}
+
+/// Fetches punch lines matching the filter in descending timestamp order, at most `count` if given.
+/// Returns `None` when running in dry-run mode.
+fn fetch_punches(client: &dyn KihoClient, filter: PunchFilter, count: Option<u32>, opts: &RunOpts) -> Result<Option<Vec<Punch>>, KihoError> {
+    if opts.dry_run {
+        msgln!("{} :: DRY RUN - Skipping HTTP GET and response prosessing!", Local::now().format(STAMP_FORMAT));
+        return Ok(None);
+    }
+    let punches = client.list_all_punches(filter, count, opts.verbose)?;
+    let changed_cnt = journal_punches(&punches, opts);
+    if changed_cnt > 0 {
+        msgln!("NOTE: {} punch line(s) changed in Kiho since last seen, use 'get journal' to see them", changed_cnt);
+    }
+    Ok(Some(punches))
+}
+
+/// Records punch lines into the local journal. Returns number of punch lines that had changed since last seen.
+fn journal_punches(punches: &[Punch], opts: &RunOpts) -> usize {
+    if punches.is_empty() {
+        return 0;
+    }
+    let mut changed = 0;
+    update_journal(opts, |journal| {
+        let (new_cnt, changed_cnt) = journal.record(punches);
+        if opts.verbose > 0 {
+            msgln!("{} :: Journal has {} new and {} changed punch line(s)", Local::now().format(STAMP_FORMAT), new_cnt, changed_cnt);
+        }
+        changed = changed_cnt;
+    });
+    changed
+}
+
+/// Loads, changes and stores the local journal. Failing to do that is not worth failing the command.
+fn update_journal(opts: &RunOpts, change: impl FnOnce(&mut Journal)) {
+    if opts.dry_run || !opts.journal {
+        return;
+    }
+    let mut journal = match Journal::load() {
+        Ok(journal) => journal,
+        Err(err) => {
+            // Storing anything now could overwrite the earlier history
+            msgln!("WARNING: Loading punch journal failed, not updating it: {}", err);
+            return;
         },
-        Some(pt) => {
-            params.push(("type", pt.to_string()));
-            format!("Latest {} worktime {} punch line(s) in ascending order", punch_count, pt)
+    };
+    change(&mut journal);
+    if let Err(err) = journal.store() {
+        msgln!("WARNING: Saving punch journal failed: {}", err);
+    }
+}
+
+/// Prints where the journal is, what it contains, and every punch line that has been changed with its earlier versions.
+fn print_journal(opts: &RunOpts) -> Result<(), KihoError> {
+    let journal = Journal::load()?;
+    msgln!("{} :: Punch journal '{}'", Local::now().format(STAMP_FORMAT), Journal::path().display());
+    let punches = journal.punches();
+    let first = punches.iter().map(|pl| pl.timestamp).min();
+    let last  = punches.iter().map(|pl| pl.timestamp).max();
+    if let (Some(first), Some(last)) = (first, last) {
+        msgln!("{} :: {} punch line(s) from {} to {}", Local::now().format(STAMP_FORMAT), punches.len(),
+            first.format("%d.%m.%Y %H:%M"), last.format("%d.%m.%Y %H:%M"));
+    }
+    let changed: Vec<&journal::JournalEntry> = journal.punches.values()
+        .filter(|entry| !entry.earlier_versions.is_empty())
+        .collect();
+    msgln!("{} :: {} punch line(s) changed in Kiho since first seen:", Local::now().format(STAMP_FORMAT), changed.len());
+    if opts.output != OutputFormat::Table {
+        output::print_records(opts.output, &changed);
+        return Ok(());
+    }
+    for entry in changed {
+        let mut versions: Vec<Punch> = entry.earlier_versions.clone();
+        versions.push(entry.punch.clone());
+        print_punch_lines_asc(&versions);
+    }
+    Ok(())
+}
+
+/// Pre-flight check done before LOGIN and LOGOUT punches: fetches the latest punch line and refuses
+/// to continue if `next` punch does not make sense after it, e.g second LOGOUT in a row.
+/// Retroactive punches made `at` some earlier time are refused also if they would be before the latest one.
+/// With `auto_logout` a new LOGIN is not refused when some task is active, but `true` is returned
+/// to tell that the active task has to be stopped first.
+fn check_punch_transition(client: &dyn KihoClient, next: PunchType, auto_logout: bool, at: Option<DateTime<FixedOffset>>, opts: &RunOpts) -> Result<bool, KihoError> {
+    if opts.dry_run {
+        msgln!("{} :: DRY RUN - Skipping latest punch line check!", Local::now().format(STAMP_FORMAT));
+        return Ok(false);
+    }
+    msgln!("{} :: Checking latest punch line before {}", Local::now().format(STAMP_FORMAT), next);
+    let latest = match latest_punch(client, opts) {
+        Ok(latest) => latest,
+        // Punch itself gets queued if Kiho API is still unreachable, so no reason to refuse it here
+        Err(err) if err.is_unreachable() => {
+            msgln!("WARNING: Skipping latest punch line check: {}", err);
+            return Ok(false);
         },
+        Err(err) => return Err(err),
     };
-    let client = reqwest::blocking::Client::new()
-        .get(KIHO_API_URL)
-        .query(&params)
-        .header(reqwest::header::AUTHORIZATION, api_key)
-        // .header(reqwest::header::CONTENT_TYPE, "application/json") HTTP GET does NOT work if this is set!
-        .header(reqwest::header::ACCEPT, "application/json")
-        .header(reqwest::header::USER_AGENT, USER_AGENT);
-        // .version(reqwest::Version::HTTP_2);
-    if CLIARGS.verbose > 1 {
-        println!("PUNCH GET REQUEST CLIENT:");
-        println!("{:#?}", client);
-        println!("PUNCH GET QUERY PARAMS:");
-        for (k,v) in params {
-            println!("{k:>10}={v}")
+    if let (Some(at), Some(pl)) = (at, &latest) {
+        // LOGOUT made first goes a second before `at`, so the LOGIN it ends has to be before `at`
+        let ends_latest = next == PunchType::LOGIN && auto_logout && pl.typ == PunchType::LOGIN;
+        if pl.timestamp > at || (ends_latest && pl.timestamp == at) {
+            let reason = format!("Latest punch line {} at {} is {} the requested time {}", pl.typ.to_string().trim(),
+                pl.timestamp.format("%d.%m.%Y %H:%M"), if pl.timestamp > at { "after" } else { "at" }, at.format("%d.%m.%Y %H:%M"));
+            return Err(KihoError::PunchState(format!("{}!\nUse '--force' to make the {} punch anyway.", reason, next)));
         }
     }
-    if CLIARGS.dry_run {
-        println!("{} :: DRY RUN - Skipping HTTP GET and response prosessing!", Local::now().format(STAMP_FORMAT));
-        return;
+    let reason = match (next, &latest) {
+        (PunchType::LOGIN, Some(pl)) if pl.typ == PunchType::LOGIN => {
+            format!("Already working on '{}' since {}", pl.desc(), pl.timestamp.format("%d.%m.%Y %H:%M"))
+        },
+        (PunchType::LOGOUT, Some(pl)) if pl.typ == PunchType::LOGOUT => {
+            format!("Already logged out since {}", pl.timestamp.format("%d.%m.%Y %H:%M"))
+        },
+        (PunchType::LOGOUT, None) => String::from("No earlier punch lines found, nothing to stop"),
+        _ => return Ok(false),
+    };
+    if next == PunchType::LOGIN && auto_logout {
+        msgln!("{} :: {}, making LOGOUT punch first", Local::now().format(STAMP_FORMAT), reason);
+        return Ok(true);
     }
-    let resp = client
-        .send()
-        .expect("FAILED TO MAKE HTTP GET");
-    // TODO [#12]: `match resp.status()`...
-    println!("{} :: HTTP response: {}", Local::now().format(STAMP_FORMAT), resp.status());
-    if CLIARGS.verbose > 1 {
-        println!("PUNCH GET RESPONSE HEADERS:");
-        println!("{:#?}", resp.headers());
-        println!("{:#?}", resp);
-    }
-    let json: serde_json::Value = resp
-        .json()
-        .expect("FAILED TO PARSE JSON RESPONSE");
-    if CLIARGS.verbose > 0 {
-        println!("PUNCH GET RESPONSE JSON:");
-        println!("{:#}", json);
+    Err(KihoError::PunchState(format!("{}!\nUse '--force' to make the {} punch anyway.", reason, next)))
+}
+
+
+/// Latest punch line, which is the last queued punch if there are any waiting in the offline queue.
+fn latest_punch(client: &dyn KihoClient, opts: &RunOpts) -> Result<Option<Punch>, KihoError> {
+    if let Some(queued) = load_queue(opts).punches.last() {
+        msgln!("{} :: Latest punch is still in the offline queue: {}", Local::now().format(STAMP_FORMAT), queued.summary());
+        return Ok(queued.punch());
     }
-    let punch_lines = json["result"].as_array()
-        .expect("FAILED TO PARSE `result` FROM THE RETURNED JSON");
-    println!("{} :: {}:", Local::now().format(STAMP_FORMAT), punch_list_header);
-    if punch_lines.len() == 0 {
-        println!("NONE FOUND!");
-        return;
+    Ok(fetch_latest_punches(client, None, 1, opts)?.and_then(|plines| plines.into_iter().next()))
+}
+
+/// Creates the punch line and returns it, or `None` if it was queued instead or running in dry-run mode.
+/// Ended session is summarized after a LOGOUT punch.
+fn http_punch_post(client: &dyn KihoClient, json_body: serde_json::Value, opts: &RunOpts) -> Result<Option<Punch>, KihoError> {
+    let punch = http_punch_create(client, json_body, opts)?;
+    if let Some(punch) = punch.as_ref().filter(|punch| punch.typ == PunchType::LOGOUT) {
+        // Punch is already created, so failing to summarize it is not an error anymore
+        if let Err(err) = print_session_summary(client, punch, opts) {
+            msgln!("WARNING: Could not summarize the ended session: {}", err);
+        }
+    }
+    Ok(punch)
+}
+
+/// Creates the punch line like `http_punch_post`, but without summarizing anything.
+fn http_punch_create(client: &dyn KihoClient, json_body: serde_json::Value, opts: &RunOpts) -> Result<Option<Punch>, KihoError> {
+    if opts.dry_run {
+        msgln!("{} :: DRY RUN - Skipping HTTP POST and response prosessing!", Local::now().format(STAMP_FORMAT));
+        return Ok(None);
+    }
+    // Sending now would get the punch to Kiho before the earlier ones and mix up their order
+    if !load_queue(opts).punches.is_empty() {
+        return queue_punch(&json_body, "Earlier punches are still queued, use 'sync' command to send them", opts).map(|_| None);
     }
-    print_punch_lines_asc(punch_lines);
+    let punch = match client.create_punch(&json_body) {
+        Ok(punch) => punch,
+        Err(err) if err.is_unreachable() => return queue_punch(&json_body, &err.to_string(), opts).map(|_| None),
+        Err(err) => return Err(err),
+    };
+    journal_punches(std::slice::from_ref(&punch), opts);
+    msgln!("{} :: Following new punch line created:", Local::now().format(STAMP_FORMAT));
+    match opts.output {
+        OutputFormat::Table => print_punch_line(&punch, None),
+        format              => output::print_record(format, &punch),
+    }
+    Ok(Some(punch))
 }
 
 
-fn http_punch_post(api_key: String, json_body: serde_json::Value) {
-    println!("{} :: Starting HTTP POST request...", Local::now().format(STAMP_FORMAT));
-    let client = reqwest::blocking::Client::new()
-        .post(KIHO_API_URL)
-        .json(&json_body)
-        .header(reqwest::header::AUTHORIZATION, api_key)
-        .header(reqwest::header::CONTENT_TYPE, "application/json")
-        .header(reqwest::header::ACCEPT, "application/json")
-        .header(reqwest::header::USER_AGENT, USER_AGENT);
-        // .version(reqwest::Version::HTTP_2);
-    if CLIARGS.verbose > 1 {
-        println!("PUNCH POST REQUEST CLIENT:");
-        println!("{:#?}", client);
+/// Offline queue, or an empty one if there are no files to use or the queue cannot be read.
+fn load_queue(opts: &RunOpts) -> PunchQueue {
+    if !opts.files {
+        return PunchQueue::default();
     }
-    if CLIARGS.dry_run {
-        println!("{} :: DRY RUN - Skipping HTTP POST and response prosessing!", Local::now().format(STAMP_FORMAT));
-        return;
+    PunchQueue::load().unwrap_or_else(|err| {
+        msgln!("WARNING: Ignoring offline queue: {}", err);
+        PunchQueue::default()
+    })
+}
+
+/// Stores punch that could not be sent into the offline queue, to be sent later with `sync` command.
+fn queue_punch(json_body: &serde_json::Value, error: &str, opts: &RunOpts) -> Result<(), KihoError> {
+    if !opts.files {
+        msgln!("WARNING: Punch could not be sent and offline queue is not in use: {}", error);
+        return Ok(());
     }
-    let resp = client
-        .send()
-        .expect("FAILED TO MAKE HTTP POST");
-    // TODO [#12]: `match resp.status()`...
-    println!("{} :: HTTP response: {}", Local::now().format(STAMP_FORMAT), resp.status());
-    if CLIARGS.verbose > 1 {
-        println!("PUNCH POST RESPONSE HEADERS:");
-        println!("{:#?}", resp.headers());
-        println!("{:#?}", resp);
-    }
-    let json: serde_json::Value = resp
-        .json()
-        .expect("FAILED TO PARSE JSON RESPONSE");
-    if CLIARGS.verbose > 0 {
-        println!("PUNCH POST RESPONSE JSON:");
-        println!("{:#}", json);
+    // Storing anything to a queue that cannot be read would lose the earlier punches
+    let mut queue = PunchQueue::load()?;
+    queue.push(json_body, error);
+    queue.store().map_err(|err| KihoError::Io(std::io::Error::other(format!("Queuing punch failed: {:?}", err))))?;
+    msgln!("WARNING: Punch could not be sent: {}", error);
+    msgln!("{} :: Punch queued, {} punch(es) now waiting for 'sync' in '{}'", Local::now().format(STAMP_FORMAT), queue.punches.len(), PunchQueue::path().display());
+    Ok(())
+}
+
+/// Sends queued punches in their original order. Stops at the first failure and keeps it and
+/// the rest of the punches queued, so that the order is never mixed up. The queue is stored after
+/// every sent punch, so that an interrupted sync never sends the same punch twice.
+fn sync_queue(client: &dyn KihoClient, args: &SyncArgs, opts: &RunOpts) -> Result<(), KihoError> {
+    let mut queue = match opts.files {
+        true  => PunchQueue::load()?,
+        false => PunchQueue::default(),
+    };
+    let store = |queue: &PunchQueue| {
+        queue.store().map_err(|err| KihoError::Io(std::io::Error::other(format!("Updating queue failed: {:?}", err))))
+    };
+    if args.drop_first && !queue.punches.is_empty() {
+        let dropped = queue.punches.remove(0);
+        msgln!("{} :: Dropping queued {} (queued {}, failed: {})", Local::now().format(STAMP_FORMAT), dropped.summary(), dropped.queued, dropped.error);
+        if opts.dry_run {
+            msgln!("{} :: DRY RUN - Keeping the dropped punch queued", Local::now().format(STAMP_FORMAT));
+        } else {
+            store(&queue)?;
+        }
     }
-    // TODO [#13]: In case of 'LOGOUT', calculate time using previous 'LOGIN'?
-    println!("{} :: Following new punch line created:", Local::now().format(STAMP_FORMAT));
-    print_punch_line(&json["result"], None);
+    if queue.punches.is_empty() {
+        msgln!("{} :: No queued punches to send", Local::now().format(STAMP_FORMAT));
+        return Ok(());
+    }
+    msgln!("{} :: Sending {} queued punch(es)", Local::now().format(STAMP_FORMAT), queue.punches.len());
+    if opts.dry_run {
+        for queued in &queue.punches {
+            msgln!("{} :: DRY RUN - Skipping queued {} (queued {})", Local::now().format(STAMP_FORMAT), queued.summary(), queued.queued);
+        }
+        return Ok(());
+    }
+    let mut result = Ok(());
+    while let Some(queued) = queue.punches.first() {
+        msgln!("{} :: Sending queued {}", Local::now().format(STAMP_FORMAT), queued.summary());
+        match client.create_punch(&queued.json()) {
+            Ok(punch) => {
+                queue.punches.remove(0);
+                store(&queue)?;
+                journal_punches(std::slice::from_ref(&punch), opts);
+                match opts.output {
+                    OutputFormat::Table => print_punch_line(&punch, None),
+                    format              => output::print_record(format, &punch),
+                }
+            },
+            Err(err) => {
+                result = Err(err);
+                break;
+            },
+        }
+    }
+    if let Err(err) = &result {
+        msgln!("WARNING: {} punch(es) still queued in '{}'", queue.punches.len(), PunchQueue::path().display());
+        if !err.is_unreachable() {
+            msgln!("WARNING: Use 'sync --drop-first' to drop the first queued punch, if Kiho keeps refusing it");
+        }
+    }
+    result
 }
 
 
-fn main() {
-    let time_start = Local::now();
-    let header     = format!("    {} v{}    ", APP_NAME, APP_VERSION);
-    println!("+{:-<1$}+", "", header.len());
-    println!("|{}|", header);
-    println!("+{:-<1$}+", "", header.len());
-    let config = load_config();
+/// Prints description, cost centre and duration of the session ended by the given LOGOUT punch,
+/// together with the total time worked today.
+fn print_session_summary(client: &dyn KihoClient, logout: &Punch, opts: &RunOpts) -> Result<(), KihoError> {
+    let logout_time = logout.timestamp;
+    let Some(mut plines) = fetch_latest_punches(client, None, 50, opts)? else {
+        return Ok(());
+    };
+    plines.retain(|pl| pl.timestamp <= logout_time);
+    plines.sort_unstable_by_key(|pl| (pl.timestamp, pl.id));
+    let sessions = pair_sessions(&plines, logout_time);
+
+    let ended = sessions.iter().rev().find(|s| s.kind == SessionKind::Work);
+    match ended {
+        None => msgln!("{} :: No matching LOGIN punch line found", Local::now().format(STAMP_FORMAT)),
+        Some(session) => {
+            let login  = &session.punch;
+            let ccc_id = login.ccc_id().map(|id| id.to_string()).unwrap_or_default();
+            msgln!("{} :: Stopped '{}' ({}, ccc id: {}) after {}", Local::now().format(STAMP_FORMAT), login.desc(), login.ccc_name(), ccc_id, format_duration(session.duration()));
+        },
+    }
+
+    let today_start = logout_time.date_naive()
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(*logout_time.offset())
+        .single()
+        .unwrap_or(logout_time);
+    let today_total = total_between(&sessions, SessionKind::Work, today_start, logout_time);
+    msgln!("{} :: Total worktime today: {}", Local::now().format(STAMP_FORMAT), format_duration(today_total));
+    Ok(())
+}
+
+
+fn print_status(client: &dyn KihoClient, args: &StatusArgs, opts: &RunOpts) -> Result<(), KihoError> {
+    // Latest punch line might be days old, but only the ones since yesterday matter for today's total
+    let Some(mut plines) = fetch_latest_punches(client, None, 50, opts)? else {
+        return Ok(());
+    };
+    // Punch ID tells the order of punch lines made within the same second
+    plines.sort_unstable_by_key(|pl| (pl.timestamp, pl.id));
+    print_status_of(&plines, args, opts);
+    Ok(())
+}
+
+/// Answers status from the latest punch lines stored when Kiho API was last used, without touching the API at all.
+fn print_status_cached(args: &StatusArgs, opts: &RunOpts) -> Result<(), KihoError> {
+    let plines = Journal::load_recent()?;
+    print_status_of(&plines, args, opts);
+    Ok(())
+}
+
+fn print_status_of(plines_asc: &[Punch], args: &StatusArgs, opts: &RunOpts) {
+    // Punches waiting in the offline queue are the latest ones, and already tell what the user is doing
+    let queued: Vec<Punch> = load_queue(opts).punches.iter().filter_map(|queued| queued.punch()).collect();
+    let status = Status::from_punches(&[plines_asc, &queued].concat(), Local::now());
+    match (&args.format, args.preset, opts.output) {
+        (Some(template), _, _)            => println!("{}", status.format(template)),
+        (None, Some(preset), _)           => println!("{}", status.preset(preset)),
+        (None, None, OutputFormat::Table) => println!("{}", status),
+        (None, None, format)              => output::print_record(format, &status),
+    }
+}
+
+
+/// Punch lines in ascending order and the sessions paired from them.
+type PairedPunches = (Vec<Punch>, Vec<Session>);
+
+/// Fetches punch lines of the period and pairs them into sessions ending at `now` at the latest. Fetching starts
+/// a day earlier to catch sessions that started before the period, so both may start before `from`.
+/// Returns `None` when running in dry-run mode.
+fn fetch_sessions(client: &dyn KihoClient, from: DateTime<FixedOffset>, to: DateTime<FixedOffset>, now: DateTime<Local>, opts: &RunOpts) -> Result<Option<PairedPunches>, KihoError> {
+    let filter = PunchFilter { punch_type: None, from: Some(from - chrono::Days::new(1)), to: Some(to) };
+    let Some(mut plines) = fetch_punches(client, filter, None, opts)? else {
+        return Ok(None);
+    };
+    plines.sort_unstable_by_key(|pl| (pl.timestamp, pl.id));
+    let sessions = pair_sessions(&plines, to.min(now.fixed_offset()));
+    Ok(Some((plines, sessions)))
+}
+
+fn print_report(client: &dyn KihoClient, args: &ReportArgs, opts: &RunOpts) -> Result<(), KihoError> {
+    let now = Local::now();
+    let (from, to) = args.period.resolve(now, Period::ThisWeek);
+    let Some((_, sessions)) = fetch_sessions(client, from, to, now, opts)? else {
+        return Ok(());
+    };
+    let per_what = if args.by_ccc { " per cost centre" } else { "" };
+    msgln!("{} :: Worked hours{} from {} to {}:", Local::now().format(STAMP_FORMAT), per_what, from.format("%d.%m.%Y %H:%M"), to.format("%d.%m.%Y %H:%M"));
+    match (opts.output, args.by_ccc) {
+        (OutputFormat::Table, true)  => report::print_costcentre_report(&sessions, from, to),
+        (OutputFormat::Table, false) => report::print_worktime_report(&sessions, from, to),
+        (format, true)               => output::print_records(format, &report::costcentre_totals(&sessions, from, to)),
+        (format, false)              => output::print_records(format, &report::day_totals(&Local, &sessions, from, to)),
+    }
+    Ok(())
+}
+
+
+fn export_punches(client: &dyn KihoClient, args: &ExportArgs, opts: &RunOpts) -> Result<(), KihoError> {
+    let now = Local::now();
+    let (from, to) = args.period.resolve(now, Period::ThisMonth);
+    let Some((mut plines, sessions)) = fetch_sessions(client, from, to, now, opts)? else {
+        return Ok(());
+    };
+    plines.retain(|pl| pl.timestamp >= from);
+    let sessions: Vec<Session> = sessions.into_iter().filter(|s| s.start >= from).collect();
+
+    let path = args.file.clone().unwrap_or_else(|| {
+        let what = match args.what {
+            ExportWhat::Punches  => "punches",
+            ExportWhat::Sessions => "sessions",
+        };
+        // Period end is exclusive, so the file is named after the last included day
+        let last_day = (to - chrono::TimeDelta::seconds(1)).date_naive();
+        format!("kiho-{}-{}-{}.{}", what, from.date_naive(), last_day, args.format.extension())
+    });
+    let mut out: Box<dyn Write> = match path.as_str() {
+        "-" => Box::new(io::stdout()),
+        _   => Box::new(io::BufWriter::new(std::fs::File::create(&path)?)),
+    };
+    let rows = match (args.format, args.what) {
+        (ExportFormat::Csv, ExportWhat::Punches)  => export::write_punches_csv(out.as_mut(), &plines, &sessions)?,
+        (ExportFormat::Csv, ExportWhat::Sessions) => export::write_sessions_csv(out.as_mut(), &sessions)?,
+        (ExportFormat::Ics, ExportWhat::Punches)  => export::write_punches_ics(out.as_mut(), &plines)?,
+        (ExportFormat::Ics, ExportWhat::Sessions) => export::write_sessions_ics(out.as_mut(), &sessions)?,
+    };
+    out.flush()?;
+    if path != "-" {
+        msgln!("{} :: Exported {} row(s) from {} to {} into '{}'", Local::now().format(STAMP_FORMAT), rows,
+            from.format("%d.%m.%Y %H:%M"), to.format("%d.%m.%Y %H:%M"), path);
+    }
+    Ok(())
+}
+
+
+fn edit_punch(client: &dyn KihoClient, args: &EditArgs, config: &KihoWtConfig, opts: &RunOpts) -> Result<(), KihoError> {
+    let ccc_id = args.ccc.as_ref().map(|ccc| find_costcentre(&config.cost_centres, ccc)).transpose()?;
+    msgln!("{} :: Editing punch line {}", Local::now().format(STAMP_FORMAT), args.id);
+    let json = create_edit_json(args.desc.as_deref(), ccc_id, args.at, opts);
+    if opts.dry_run {
+        msgln!("{} :: DRY RUN - Skipping HTTP PUT and response prosessing!", Local::now().format(STAMP_FORMAT));
+        return Ok(());
+    }
+    let punch = client.update_punch(args.id, &json)?;
+    journal_punches(std::slice::from_ref(&punch), opts);
+    msgln!("{} :: Punch line changed to following:", Local::now().format(STAMP_FORMAT));
+    match opts.output {
+        OutputFormat::Table => print_punch_line(&punch, None),
+        format              => output::print_record(format, &punch),
+    }
+    Ok(())
+}
+
+fn delete_punch(client: &dyn KihoClient, args: &DeleteArgs, opts: &RunOpts) -> Result<(), KihoError> {
+    // Showing what is about to be deleted is possible only if the punch line has been seen before
+    let known = Journal::load().ok()
+        .and_then(|journal| journal.punches().into_iter().find(|pl| pl.id == args.id));
+    if let Some(punch) = &known {
+        msgln!("{} :: Punch line to delete:", Local::now().format(STAMP_FORMAT));
+        if opts.output == OutputFormat::Table {
+            print_punch_lines_asc(std::slice::from_ref(punch));
+        }
+    }
+    if !args.yes && !ask_confirmation(&format!("Really delete punch line {}", args.id)) {
+        msgln!("EXITING...");
+        return Ok(());
+    }
+    if opts.dry_run {
+        msgln!("{} :: DRY RUN - Skipping HTTP DELETE!", Local::now().format(STAMP_FORMAT));
+        return Ok(());
+    }
+    client.delete_punch(args.id)?;
+    update_journal(opts, |journal| journal.mark_deleted(args.id));
+    msgln!("{} :: Punch line {} deleted", Local::now().format(STAMP_FORMAT), args.id);
+    Ok(())
+}
+
+/// Description and cost centre of the task to start, asked if not given on the command line.
+/// Selected cost centre is remembered as the default for the next time.
+fn select_task(punch: &PunchDesc, ccc: Option<&String>, config: &KihoWtConfig, opts: &RunOpts) -> Result<(PunchDesc, u32), KihoError> {
+    let punch_desc = match &punch.desc {
+        None    => ask_recurring_desc(config.recurring_tasks.clone()),
+        Some(_) => punch.clone(),
+    };
+    let mut state = load_state(opts);
+    let punch_ccc = match ccc {
+        Some(ccc) => find_costcentre(&config.cost_centres, ccc)?,
+        None      => match match_costcentre_rule(&config.ccc_rules, &punch_desc.to_string(), opts) {
+            Some(id) => id,
+            None     => ask_costcentre(&config.cost_centres, state.last_ccc_id)?,
+        },
+    };
+    state.last_ccc_id = Some(punch_ccc);
+    store_state(&state, opts);
+    Ok((punch_desc, punch_ccc))
+}
+
+/// Starts a new task. With `--auto-logout` the active task is stopped first.
+fn start_task(client: &dyn KihoClient, args: &StartArgs, config: &KihoWtConfig, opts: &RunOpts) -> Result<(), KihoError> {
+    // Asking everything before any punches, so that cancelling never leaves user logged out
+    let (punch_desc, punch_ccc) = select_task(&args.punch, args.ccc.as_ref(), config, opts)?;
+    let logout_first = !args.force && check_punch_transition(client, PunchType::LOGIN, args.auto_logout, args.at, opts)?;
+    let start_msg = format!("{} :: Starting '{}' (ccc id: {}){}", Local::now().format(STAMP_FORMAT), punch_desc, punch_ccc, at_str(args.at));
+    let login_json = create_punch_json(PunchType::LOGIN, Some(punch_desc), Some(punch_ccc), args.at, opts);
+    if logout_first {
+        // Retroactive LOGOUT is made a second before the LOGIN, so that they never have the very same timestamp
+        let logout_at = args.at.map(|at| at - chrono::TimeDelta::seconds(1));
+        let logout_json = create_punch_json(PunchType::LOGOUT, None, None, logout_at, opts);
+        if http_punch_post(client, logout_json, opts)?.is_none() {
+            // LOGOUT got queued, so the LOGIN has to follow it there to keep the order
+            return queue_punch(&login_json, "Preceding LOGOUT punch was queued", opts);
+        }
+    }
+    msgln!("{}", start_msg);
+    http_punch_post(client, login_json, opts)?;
+    Ok(())
+}
+
+/// Stops the active task and starts a new one. If starting fails after the LOGOUT punch was created,
+/// the LOGOUT is deleted again so that the earlier task goes on as if nothing happened.
+fn switch_task(client: &dyn KihoClient, args: &SwitchArgs, config: &KihoWtConfig, opts: &RunOpts) -> Result<(), KihoError> {
+    // Asking everything before any punches, so that cancelling never leaves user logged out
+    let (punch_desc, punch_ccc) = select_task(&args.punch, args.ccc.as_ref(), config, opts)?;
+    let working = args.force || opts.dry_run || match latest_punch(client, opts) {
+        Ok(latest) => latest.is_some_and(|pl| pl.typ == PunchType::LOGIN),
+        // Both punches get queued anyway if Kiho API is still unreachable
+        Err(err) if err.is_unreachable() => true,
+        Err(err) => return Err(err),
+    };
+    let logout_json = match working {
+        true  => Some(create_punch_json(PunchType::LOGOUT, None, None, None, opts)),
+        false => {
+            msgln!("{} :: Not working on anything, so nothing to stop", Local::now().format(STAMP_FORMAT));
+            None
+        },
+    };
+    msgln!("{} :: Switching to '{}' (ccc id: {})", Local::now().format(STAMP_FORMAT), punch_desc, punch_ccc);
+    // LOGIN with the same timestamp still follows the LOGOUT, as punches are ordered by their ids too
+    let login_json = create_punch_json(PunchType::LOGIN, Some(punch_desc), Some(punch_ccc), None, opts);
+    if opts.dry_run {
+        msgln!("{} :: DRY RUN - Skipping HTTP POSTs and response prosessing!", Local::now().format(STAMP_FORMAT));
+        return Ok(());
+    }
+
+    let logout = match logout_json {
+        None       => None,
+        // Switching is quick, so the ended session is not summarized to save one request
+        Some(json) => match http_punch_create(client, json, opts)? {
+            Some(punch) => Some(punch),
+            // LOGOUT got queued, so the LOGIN has to follow it there to keep the order
+            None => return queue_punch(&login_json, "Preceding LOGOUT punch was queued", opts),
+        },
+    };
+    let Err(err) = http_punch_post(client, login_json, opts) else {
+        return Ok(());
+    };
+    let Some(logout) = logout else {
+        return Err(err);
+    };
+    msgln!("WARNING: Starting the new task failed, rolling back LOGOUT punch line {}", logout.id);
+    match client.delete_punch(logout.id) {
+        Ok(()) => {
+            update_journal(opts, |journal| journal.mark_deleted(logout.id));
+            msgln!("{} :: LOGOUT punch line {} deleted, the earlier task is still active", Local::now().format(STAMP_FORMAT), logout.id);
+        },
+        Err(rollback_err) => {
+            msgln!("WARNING: Rolling back failed, you are now LOGGED OUT: {}", rollback_err);
+            msgln!("WARNING: Use 'start' command to start the new task");
+        },
+    }
+    Err(err)
+}
+
+/// Tells when retroactive punch is made, e.g " at 17.10.2024 08:15", or nothing for punches made now.
+fn at_str(at: Option<DateTime<FixedOffset>>) -> String {
+    at.map(|at| format!(" at {}", at.format("%d.%m.%Y %H:%M"))).unwrap_or_default()
+}
+
+/// Banner and log lines go to stderr when stdout is reserved for JSON results or exported file,
+/// and nowhere when formatting status for shell prompts and status bars, which have no use for them.
+fn msg_target(args: &CliArgs) -> output::MsgTarget {
+    match &args.command {
+        CliCommands::Status(status) if status.formatted()               => output::MsgTarget::Nowhere,
+        CliCommands::Export(export) if export.file.as_deref() == Some("-") => output::MsgTarget::Stderr,
+        _ if args.output == OutputFormat::Table                          => output::MsgTarget::Stdout,
+        _                                                                => output::MsgTarget::Stderr,
+    }
+}
+
+/// Environment variable for the API key of the named profile, e.g `KIHO_API_KEY_CLIENT_EU` for 'client-eu'.
+fn api_key_env_var(profile: &str) -> String {
+    let suffix: String = profile.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("KIHO_API_KEY_{}", suffix)
+}
+
+/// Reads API key from `KIHO_API_KEY` environment variable, or from `api_key_cmd`, `api_key_file` or `api_key`
+/// in the configuration, in this order. With a profile its own environment variable comes first, and its own
+/// key source in the configuration before `KIHO_API_KEY`. Refuses the placeholder of a new configuration.
+fn api_key(config: &KihoWtConfig) -> Result<String, KihoError> {
+    let profile_var = PROFILE.get().map(|profile| api_key_env_var(profile));
+    let env_key = profile_var.as_deref().into_iter()
+        .chain((!config.profile_api_key).then_some("KIHO_API_KEY"))
+        .find_map(|var| std::env::var(var).ok().map(|key| (var, key)));
+    let (source, key) = if let Some((var, key)) = env_key {
+        (format!("{} environment variable", var), key)
+    } else if let Some(cmd) = &config.api_key_cmd {
+        let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        let output = std::process::Command::new(shell).args([flag, cmd])
+            .stdin(std::process::Stdio::null())
+            .output()
+            .map_err(|err| KihoError::ApiKey(format!("Running api_key_cmd '{}' failed: {}", cmd, err)))?;
+        if !output.status.success() {
+            return Err(KihoError::ApiKey(format!("Command api_key_cmd '{}' failed ({}) {}",
+                cmd, output.status, String::from_utf8_lossy(&output.stderr).trim())));
+        }
+        // Only the first line b/c e.g `pass` prints other details of the secret after it
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        (format!("api_key_cmd '{}'", cmd), stdout.lines().next().unwrap_or_default().to_string())
+    } else if let Some(path) = &config.api_key_file {
+        let key = std::fs::read_to_string(path)
+            .map_err(|err| KihoError::ApiKey(format!("Reading api_key_file '{}' failed: {}", path, err)))?;
+        (format!("api_key_file '{}'", path), key)
+    } else {
+        (String::from("'api_key' of the configuration"), config.api_key.clone())
+    };
+    let key = key.trim();
+    if key.is_empty() || key == API_KEY_PLACEHOLDER {
+        return Err(KihoError::ApiKey(format!("API key is not set in {}, ask one from Kiho administrator", source)));
+    }
     if CLIARGS.verbose > 0 {
-        println!("API URL:     {}", KIHO_API_URL);
-        println!("USER AGENT:  {}", USER_AGENT);
-        println!("Config path: {}", confy::get_configuration_file_path(CONFIG_NAME, None)
-                 .expect("Getting configuration file path failed").display());
-        println!("Dry-run:     {}", CLIARGS.dry_run);
-        println!("Verbosity:   {}", CLIARGS.verbose);
-        println!("Start time:  {}", time_start.format(STAMP_FORMAT));
+        msgln!("{} :: Using API key from {}", Local::now().format(STAMP_FORMAT), source);
     }
-    if CLIARGS.dry_run && CLIARGS.verbose == 0 {
-        println!("NOTE: This is a DRY-RUN!");
+    Ok(key.to_string())
+}
+
+/// Command line option and environment variable override the configured API URL.
+fn api_url(config: &KihoWtConfig) -> &str {
+    CLIARGS.api_url.as_deref().unwrap_or(&config.api_url)
+}
+
+fn run_command(config: KihoWtConfig) -> Result<(), KihoError> {
+    let opts = &RunOpts::from_args(&CLIARGS);
+    // Cached status is meant to be fast, so it's answered before even building the HTTP client
+    if let CliCommands::Status(args) = &CLIARGS.command {
+        if args.cached {
+            return print_status_cached(args, opts);
+        }
     }
+    let client: Box<dyn KihoClient> = match (CLIARGS.fake_api, CLIARGS.offline) {
+        (true, _)      => Box::new(MemoryClient::new(Vec::new())),
+        (false, true)  => Box::new(OfflineClient::new(Journal::load()?.punches())),
+        (false, false) if CLIARGS.command.uses_api() => Box::new(HttpClient::new(&api_key(&config)?, api_url(&config), CLIARGS.verbose)),
+        // Never called, so the API key is not needed either
+        (false, false) => Box::new(HttpClient::new("", api_url(&config), CLIARGS.verbose)),
+    };
+    let client = client.as_ref();
     match &CLIARGS.command {
         CliCommands::Get { what } => match what {
             // Using `:#?` gives pretty-formatted (debug) output
             CliGetWhat::CCC     => println!("Available 'Customer Cost Centres': {:#?}", config.cost_centres),
             CliGetWhat::Tasks   => println!("Available 'Recurring Tasks': {:#?}", config.recurring_tasks),
+            CliGetWhat::Breaks  => println!("Available 'Break Types': {:#?}", config.break_types),
+            CliGetWhat::Rules   => println!("Configured 'Cost Centre Rules': {:#?}", config.ccc_rules),
+            CliGetWhat::Profiles => println!("Configured 'Profiles': {:#?}", config.profiles.keys().collect::<Vec<_>>()),
             CliGetWhat::Config  => println!("Current WHOLE config: {:#?}", config),
             CliGetWhat::JSON    => print_example_jsons(),
-            CliGetWhat::Latest { cnt, typ } => get_latest_punch(config.api_key, *typ, *cnt),
-        },
-        CliCommands::Break => {
-            println!("{} :: Starting a BREAK", Local::now().format(STAMP_FORMAT));
-            todo!("Ask break type");
-            // let _json = create_punch_json(PunchType::BREAK, None, None);
+            CliGetWhat::Journal => print_journal(opts)?,
+            CliGetWhat::Latest { cnt, typ } => get_latest_punch(client, *typ, *cnt, opts)?,
+            CliGetWhat::Range { period, typ } => get_punch_range(client, period, *typ, opts)?,
         },
-        CliCommands::Start(desc) => {
-            let punch_desc = match &desc.desc {
-                None    => ask_recurring_desc(config.recurring_tasks),
-                Some(_) => desc.clone(),
+        CliCommands::Status(args) => print_status(client, args, opts)?,
+        CliCommands::Report(args) => print_report(client, args, opts)?,
+        CliCommands::Export(args) => export_punches(client, args, opts)?,
+        CliCommands::Break(brk) => {
+            let punch_desc = match &brk.typ {
+                None      => ask_break_type(config.break_types)?,
+                Some(typ) => PunchDesc { desc: Some(typ.clone()) },
             };
-            let punch_ccc = ask_costcentre(punch_desc.to_string().as_str());
-            println!("{} :: Starting '{}' (ccc id: {})", Local::now().format(STAMP_FORMAT), punch_desc, punch_ccc);
-            // TODO [10]: Get latest worktime punch line and ERROR OUT if it is 'LOGIN' - OR make LOGOUT punch before LOGIN?
-            let json = create_punch_json(PunchType::LOGIN, Some(punch_desc), Some(punch_ccc));
-            http_punch_post(config.api_key, json);
+            msgln!("{} :: Starting a BREAK '{}'", Local::now().format(STAMP_FORMAT), punch_desc);
+            let json = create_punch_json(PunchType::BREAK, Some(punch_desc), None, None, opts);
+            http_punch_post(client, json, opts)?;
         },
-        CliCommands::Stop => {
-            // TODO [10]: Get latest worktime description and error out if it is NOT of type 'LOGIN'
-            println!("{} :: Stopping worktime", Local::now().format(STAMP_FORMAT));
-            let json = create_punch_json(PunchType::LOGOUT, None, None);
-            http_punch_post(config.api_key, json);
+        CliCommands::Start(args) => start_task(client, args, &config, opts)?,
+        CliCommands::Stop(args) => {
+            if !args.force {
+                check_punch_transition(client, PunchType::LOGOUT, false, args.at, opts)?;
+            }
+            msgln!("{} :: Stopping worktime{}", Local::now().format(STAMP_FORMAT), at_str(args.at));
+            let json = create_punch_json(PunchType::LOGOUT, None, None, args.at, opts);
+            http_punch_post(client, json, opts)?;
         },
+        CliCommands::Switch(args) => switch_task(client, args, &config, opts)?,
+        CliCommands::Edit(args) => edit_punch(client, args, &config, opts)?,
+        CliCommands::Delete(args) => delete_punch(client, args, opts)?,
+        CliCommands::Sync(args) => sync_queue(client, args, opts)?,
+    }
+    Ok(())
+}
+
+
+fn main() {
+    let time_start = Local::now();
+    // Parsing the arguments also decides where the messages go
+    Lazy::force(&CLIARGS);
+    let header     = format!("    {} v{}    ", APP_NAME, APP_VERSION);
+    msgln!("+{:-<1$}+", "", header.len());
+    msgln!("|{}|", header);
+    msgln!("+{:-<1$}+", "", header.len());
+    let config = load_config().unwrap_or_else(|err| {
+        eprintln!("ERROR: {}", err);
+        std::process::exit(err.exit_code());
+    });
+    if CLIARGS.verbose > 0 {
+        msgln!("Profile:     {}", CLIARGS.profile.as_deref().unwrap_or("-"));
+        msgln!("API URL:     {}", api_url(&config));
+        msgln!("USER AGENT:  {}", USER_AGENT);
+        msgln!("Config path: {}", confy::get_configuration_file_path(CONFIG_NAME, None)
+                 .expect("Getting configuration file path failed").display());
+        msgln!("Dry-run:     {}", CLIARGS.dry_run);
+        msgln!("Verbosity:   {}", CLIARGS.verbose);
+        msgln!("Start time:  {}", time_start.format(STAMP_FORMAT));
+    }
+    if CLIARGS.dry_run && CLIARGS.verbose == 0 {
+        msgln!("NOTE: This is a DRY-RUN!");
+    }
+    let queue = match RunOpts::from_args(&CLIARGS).files {
+        true  => PunchQueue::load(),
+        false => Ok(PunchQueue::default()),
+    };
+    match queue {
+        Ok(queue) if !queue.punches.is_empty() && !matches!(CLIARGS.command, CliCommands::Sync(_)) => {
+            msgln!("WARNING: {} punch(es) waiting in the offline queue, use 'sync' command to send them", queue.punches.len());
+        },
+        Ok(_) => (),
+        Err(err) => msgln!("WARNING: {}", err),
+    }
+    if let Err(err) = run_command(config) {
+        eprintln!("ERROR: {}", err);
+        std::process::exit(err.exit_code());
     }
 
     if CLIARGS.verbose > 0 {
         let time_stop = Local::now();
-        println!("");
-        println!("Stop time: {}", time_stop.format(STAMP_FORMAT));
-        println!("Elapsed:   {}", time_stop-time_start);
+        msgln!();
+        msgln!("Stop time: {}", time_stop.format(STAMP_FORMAT));
+        msgln!("Elapsed:   {}", time_stop-time_start);
     }
-    println!("");
+    msgln!();
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_opts() -> RunOpts {
+        RunOpts { dry_run: false, verbose: 0, output: OutputFormat::Table, journal: false, files: false }
+    }
+
+    fn test_punch(id: u64, typ: PunchType, hours_ago: i64, desc: &str) -> Punch {
+        let timestamp = Local::now().fixed_offset() - chrono::TimeDelta::hours(hours_ago);
+        serde_json::from_value(json!({
+            "id": id, "type": typ, "description": desc, "timestamp": timestamp,
+            "customerCostcentre": { "id": 101, "name": "Rusting" },
+        })).unwrap()
+    }
+
+    /// Punch lines of the client in ascending order.
+    fn punches_of(client: &dyn KihoClient) -> Vec<Punch> {
+        let mut plines = client.list_all_punches(PunchFilter::default(), None, 0).unwrap();
+        plines.sort_unstable_by_key(|pl| (pl.timestamp, pl.id));
+        plines
+    }
+
+    fn switch_args(desc: &str) -> SwitchArgs {
+        SwitchArgs { punch: PunchDesc { desc: Some(desc.to_string()) }, ccc: Some(String::from("202")), force: false }
+    }
+
+    /// Fails creating LOGIN punches, e.g like Kiho refusing a cost centre.
+    struct LoginRefusingClient(MemoryClient);
+    impl KihoClient for LoginRefusingClient {
+        fn list_punches(&self, query: &client::PunchQuery) -> Result<Vec<Punch>, KihoError> {
+            self.0.list_punches(query)
+        }
+        fn create_punch(&self, new_punch: &serde_json::Value) -> Result<Punch, KihoError> {
+            match new_punch["newPunch"]["type"].as_str() {
+                Some("LOGIN") => Err(KihoError::Validation(reqwest::StatusCode::UNPROCESSABLE_ENTITY, String::from("refused"))),
+                _             => self.0.create_punch(new_punch),
+            }
+        }
+        fn update_punch(&self, id: u64, changes: &serde_json::Value) -> Result<Punch, KihoError> {
+            self.0.update_punch(id, changes)
+        }
+        fn delete_punch(&self, id: u64) -> Result<(), KihoError> {
+            self.0.delete_punch(id)
+        }
+    }
+
+    #[test]
+    fn costcentre_found_by_id_or_name() {
+        let cccs = std::collections::HashMap::from([(String::from("101"), String::from("Rusting"))]);
+        assert_eq!(find_costcentre(&cccs, "101").unwrap(), 101);
+        assert_eq!(find_costcentre(&cccs, "rusting").unwrap(), 101);
+        assert_eq!(find_costcentre(&cccs, "202").unwrap(), 202);
+        assert!(matches!(find_costcentre(&cccs, "nosuchname"), Err(KihoError::Config(_))));
+        let invalid = std::collections::HashMap::from([(String::from("abc"), String::from("Rusting"))]);
+        assert!(matches!(find_costcentre(&invalid, "101"), Err(KihoError::Config(_))));
+    }
+
+    fn test_rule(pattern: &str, regex: bool, ccc_id: u32) -> CccRule {
+        CccRule { pattern: pattern.to_string(), regex, ccc_id, compiled: None }
+    }
+
+    #[test]
+    fn first_matching_costcentre_rule_wins() {
+        let config = KihoWtConfig {
+            ccc_rules: vec![test_rule("(?i)^iso ?27", true, 1), test_rule("review", false, 2), test_rule("Code", false, 3)],
+            ..Default::default()
+        }.compiled().unwrap();
+        assert_eq!(match_costcentre_rule(&config.ccc_rules, "ISO27001 audit", &test_opts()), Some(1));
+        assert_eq!(match_costcentre_rule(&config.ccc_rules, "Code review", &test_opts()), Some(2));
+        assert_eq!(match_costcentre_rule(&config.ccc_rules, "Code", &test_opts()), Some(3));
+        assert_eq!(match_costcentre_rule(&config.ccc_rules, "Lunch", &test_opts()), None);
+    }
+
+    #[test]
+    fn invalid_rule_regex_refused_when_loading() {
+        let config = KihoWtConfig { ccc_rules: vec![test_rule("(unclosed", true, 1)], ..Default::default() };
+        assert!(matches!(config.compiled(), Err(KihoError::Config(_))));
+    }
+
+    #[test]
+    fn default_configuration_has_no_rules() {
+        let config = KihoWtConfig::default();
+        assert_eq!(match_costcentre_rule(&config.ccc_rules, &config.recurring_tasks[0], &test_opts()), None);
+    }
+
+    #[test]
+    fn profile_api_key_source_replaces_top_level_ones() {
+        let config = KihoWtConfig {
+            api_key_cmd: Some(String::from("pass show kiho")),
+            profiles: std::collections::BTreeMap::from([
+                (String::from("client-eu"), KihoWtProfile { api_key_file: Some(String::from("client.key")), ..Default::default() }),
+                (String::from("other"), KihoWtProfile::default()),
+            ]),
+            ..Default::default()
+        };
+        let other = config.with_profile("other").unwrap();
+        assert!(!other.profile_api_key);
+        assert_eq!(other.api_key_cmd.as_deref(), Some("pass show kiho"));
+        let client = other.with_profile("client-eu").unwrap();
+        assert!(client.profile_api_key);
+        assert_eq!(client.api_key_cmd, None);
+        assert_eq!(client.api_key_file.as_deref(), Some("client.key"));
+        assert_eq!(api_key_env_var("client-eu"), "KIHO_API_KEY_CLIENT_EU");
+    }
+
+    #[test]
+    fn login_refused_when_already_working() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 2, "Rusting it out")]);
+        let result = check_punch_transition(&client, PunchType::LOGIN, false, None, &test_opts());
+        assert!(matches!(result, Err(KihoError::PunchState(_))));
+    }
+
+    #[test]
+    fn login_allowed_after_logout() {
+        let client = MemoryClient::new(vec![
+            test_punch(1, PunchType::LOGIN, 3, "Rusting it out"),
+            test_punch(2, PunchType::LOGOUT, 2, ""),
+        ]);
+        assert!(check_punch_transition(&client, PunchType::LOGIN, false, None, &test_opts()).is_ok());
+    }
+
+    #[test]
+    fn logout_refused_without_punches_or_after_logout() {
+        let client = MemoryClient::new(Vec::new());
+        let result = check_punch_transition(&client, PunchType::LOGOUT, false, None, &test_opts());
+        assert!(matches!(result, Err(KihoError::PunchState(_))));
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGOUT, 1, "")]);
+        let result = check_punch_transition(&client, PunchType::LOGOUT, false, None, &test_opts());
+        assert!(matches!(result, Err(KihoError::PunchState(_))));
+    }
+
+    #[test]
+    fn retroactive_punch_refused_before_latest() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 1, "Rusting it out")]);
+        let at = Some(Local::now().fixed_offset() - chrono::TimeDelta::hours(2));
+        let result = check_punch_transition(&client, PunchType::LOGOUT, false, at, &test_opts());
+        assert!(matches!(result, Err(KihoError::PunchState(_))));
+    }
+
+    fn start_args(desc: &str, auto_logout: bool, at: Option<DateTime<FixedOffset>>) -> StartArgs {
+        StartArgs {
+            punch: PunchDesc { desc: Some(desc.to_string()) }, ccc: Some(String::from("202")),
+            force: false, auto_logout, at,
+        }
+    }
+
+    #[test]
+    fn start_refused_when_already_working() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 1, "Rusting it out")]);
+        let result = start_task(&client, &start_args("Code review", false, None), &KihoWtConfig::default(), &test_opts());
+        assert!(matches!(result, Err(KihoError::PunchState(_))));
+        assert_eq!(punches_of(&client).len(), 1);
+    }
+
+    #[test]
+    fn start_with_auto_logout_stops_active_task_first() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 2, "Rusting it out")]);
+        let at = Some(Local::now().fixed_offset() - chrono::TimeDelta::hours(1));
+        start_task(&client, &start_args("Code review", true, at), &KihoWtConfig::default(), &test_opts()).unwrap();
+        let plines = punches_of(&client);
+        let types: Vec<PunchType> = plines.iter().map(|pl| pl.typ).collect();
+        assert_eq!(types, [PunchType::LOGIN, PunchType::LOGOUT, PunchType::LOGIN]);
+        assert!(plines[1].timestamp < plines[2].timestamp);
+        assert_eq!(plines[2].desc(), "Code review");
+    }
+
+    #[test]
+    fn start_with_auto_logout_refused_at_time_of_active_task() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 2, "Rusting it out")]);
+        let at = Some(punches_of(&client)[0].timestamp);
+        let result = start_task(&client, &start_args("Code review", true, at), &KihoWtConfig::default(), &test_opts());
+        assert!(matches!(result, Err(KihoError::PunchState(_))));
+        assert_eq!(punches_of(&client).len(), 1);
+    }
+
+    #[test]
+    fn punch_post_creates_punch_line() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 1, "Rusting it out")]);
+        let json = create_punch_json(PunchType::LOGOUT, None, None, None, &test_opts());
+        let punch = http_punch_post(&client, json, &test_opts()).unwrap().unwrap();
+        assert_eq!(punch.typ, PunchType::LOGOUT);
+        assert_eq!(punches_of(&client).last().unwrap().id, punch.id);
+    }
+
+    #[test]
+    fn punch_post_skipped_in_dry_run() {
+        let client = MemoryClient::new(Vec::new());
+        let opts = RunOpts { dry_run: true, ..test_opts() };
+        let json = create_punch_json(PunchType::BREAK, Some(PunchDesc { desc: Some(String::from("Lunch")) }), None, None, &opts);
+        assert!(http_punch_post(&client, json, &opts).unwrap().is_none());
+        assert!(punches_of(&client).is_empty());
+    }
+
+    #[test]
+    fn switch_stops_active_task_and_starts_new_one() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 1, "Rusting it out")]);
+        switch_task(&client, &switch_args("Code review"), &KihoWtConfig::default(), &test_opts()).unwrap();
+        let plines = punches_of(&client);
+        let types: Vec<PunchType> = plines.iter().map(|pl| pl.typ).collect();
+        assert_eq!(types, [PunchType::LOGIN, PunchType::LOGOUT, PunchType::LOGIN]);
+        assert_eq!(plines[2].desc(), "Code review");
+        assert_eq!(plines[2].ccc_id(), Some(202));
+    }
+
+    #[test]
+    fn switch_only_starts_when_not_working() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGOUT, 1, "")]);
+        switch_task(&client, &switch_args("Code review"), &KihoWtConfig::default(), &test_opts()).unwrap();
+        let types: Vec<PunchType> = punches_of(&client).iter().map(|pl| pl.typ).collect();
+        assert_eq!(types, [PunchType::LOGOUT, PunchType::LOGIN]);
+    }
+
+    #[test]
+    fn switch_rolls_back_logout_when_login_fails() {
+        let client = LoginRefusingClient(MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 1, "Rusting it out")]));
+        let result = switch_task(&client, &switch_args("Code review"), &KihoWtConfig::default(), &test_opts());
+        assert!(matches!(result, Err(KihoError::Validation(_, _))));
+        let plines = punches_of(&client);
+        assert_eq!(plines.len(), 1);
+        assert_eq!(plines[0].typ, PunchType::LOGIN);
+    }
+
+    fn edit_args(id: u64, desc: Option<&str>, ccc: Option<&str>, at: Option<DateTime<FixedOffset>>) -> EditArgs {
+        EditArgs { id, desc: desc.map(String::from), ccc: ccc.map(String::from), at }
+    }
+
+    #[test]
+    fn edit_changes_only_given_fields() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 2, "Rusting it out")]);
+        let original = punches_of(&client).remove(0);
+        edit_punch(&client, &edit_args(1, Some("Rusting harder"), Some("202"), None), &KihoWtConfig::default(), &test_opts()).unwrap();
+        let edited = punches_of(&client).remove(0);
+        assert_eq!((edited.desc(), edited.ccc_id(), edited.timestamp), ("Rusting harder", Some(202), original.timestamp));
+
+        let at = DateTime::parse_from_rfc3339("2024-09-02T08:15:00+03:00").unwrap();
+        edit_punch(&client, &edit_args(1, None, None, Some(at)), &KihoWtConfig::default(), &test_opts()).unwrap();
+        let edited = punches_of(&client).remove(0);
+        assert_eq!((edited.desc(), edited.timestamp), ("Rusting harder", at));
+    }
+
+    #[test]
+    fn edit_refused_for_unknown_punch_or_costcentre_and_skipped_in_dry_run() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 2, "Rusting it out")]);
+        let result = edit_punch(&client, &edit_args(99, Some("Nothing"), None, None), &KihoWtConfig::default(), &test_opts());
+        assert!(matches!(result, Err(KihoError::Validation(_, _))));
+        let result = edit_punch(&client, &edit_args(1, None, Some("nosuchname"), None), &KihoWtConfig::default(), &test_opts());
+        assert!(matches!(result, Err(KihoError::Config(_))));
+        let opts = RunOpts { dry_run: true, ..test_opts() };
+        edit_punch(&client, &edit_args(1, Some("Nothing"), None, None), &KihoWtConfig::default(), &opts).unwrap();
+        assert_eq!(punches_of(&client)[0].desc(), "Rusting it out");
+    }
+
+    #[test]
+    fn delete_removes_punch_line() {
+        let client = MemoryClient::new(vec![test_punch(1, PunchType::LOGIN, 2, "Rusting it out"), test_punch(2, PunchType::LOGOUT, 1, "")]);
+        delete_punch(&client, &DeleteArgs { id: 2, yes: true }, &RunOpts { dry_run: true, ..test_opts() }).unwrap();
+        assert_eq!(punches_of(&client).len(), 2);
+        delete_punch(&client, &DeleteArgs { id: 2, yes: true }, &test_opts()).unwrap();
+        let ids: Vec<u64> = punches_of(&client).iter().map(|pl| pl.id).collect();
+        assert_eq!(ids, [1]);
+        let result = delete_punch(&client, &DeleteArgs { id: 2, yes: true }, &test_opts());
+        assert!(matches!(result, Err(KihoError::Validation(_, _))));
+    }
+}
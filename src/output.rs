@@ -0,0 +1,81 @@
+//
+// Output format of the command results, i.e human readable tables or JSON for scripts.
+//
+// When results are printed as JSON, stdout is reserved for the results alone. Everything else,
+// e.g the banner and timestamped log lines, is printed to stderr using `msg!` and `msgln!` macros.
+// Formatted status for shell prompts and status bars leaves out everything else altogether.
+//
+
+use serde::Serialize;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable tables and log lines
+    Table,
+    /// Pretty-printed JSON array, or object for a single created punch line
+    Json,
+    /// One compact JSON object per line
+    Jsonl,
+}
+
+/// Where `msg!` and `msgln!` macros print to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MsgTarget {
+    Stdout,
+    Stderr,
+    Nowhere,
+}
+
+// Set once in `main` based on the command line. Messages go to stdout until then, e.g in tests.
+static MSG_TARGET: once_cell::sync::OnceCell<MsgTarget> = once_cell::sync::OnceCell::new();
+
+pub fn set_msg_target(target: MsgTarget) {
+    let _ = MSG_TARGET.set(target);
+}
+
+pub fn msg_target() -> MsgTarget {
+    MSG_TARGET.get().copied().unwrap_or(MsgTarget::Stdout)
+}
+
+/// Like `print!`, but printed to stderr when stdout is reserved for results.
+macro_rules! msg {
+    ($($arg:tt)*) => {
+        match crate::output::msg_target() {
+            crate::output::MsgTarget::Stdout  => print!($($arg)*),
+            crate::output::MsgTarget::Stderr  => eprint!($($arg)*),
+            crate::output::MsgTarget::Nowhere => {},
+        }
+    };
+}
+
+/// Like `println!`, but printed to stderr when stdout is reserved for results.
+macro_rules! msgln {
+    ($($arg:tt)*) => {
+        match crate::output::msg_target() {
+            crate::output::MsgTarget::Stdout  => println!($($arg)*),
+            crate::output::MsgTarget::Stderr  => eprintln!($($arg)*),
+            crate::output::MsgTarget::Nowhere => {},
+        }
+    };
+}
+
+/// Prints list of results, e.g punch lines or report rows. Not meant for `Table` format,
+/// which is different for each kind of result.
+pub fn print_records<T: Serialize>(format: OutputFormat, records: &[T]) {
+    match format {
+        OutputFormat::Table => {},
+        OutputFormat::Json  => println!("{}", serde_json::to_string_pretty(records).expect("Serializing JSON failed")),
+        OutputFormat::Jsonl => for record in records {
+            println!("{}", serde_json::to_string(record).expect("Serializing JSON failed"));
+        },
+    }
+}
+
+/// Prints single result, e.g created punch line. Not meant for `Table` format.
+pub fn print_record<T: Serialize>(format: OutputFormat, record: &T) {
+    match format {
+        OutputFormat::Table => {},
+        OutputFormat::Json  => println!("{}", serde_json::to_string_pretty(record).expect("Serializing JSON failed")),
+        OutputFormat::Jsonl => println!("{}", serde_json::to_string(record).expect("Serializing JSON failed")),
+    }
+}
@@ -0,0 +1,230 @@
+//
+// Time periods used for querying punch history, e.g `today` or `--from 2024-09-01 --to 2024-09-30`.
+//
+// Periods are half-open ranges `[from, to)` in local time and weeks start on Monday.
+//
+
+use chrono::prelude::*;
+use chrono::{Days, Months, TimeDelta};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Period {
+    Today,
+    Yesterday,
+    ThisWeek,
+    LastWeek,
+    ThisMonth,
+    LastMonth,
+}
+impl Period {
+    /// Returns `[from, to)` range of the period relative to the given day.
+    fn range(&self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let week_start  = today - Days::new(today.weekday().num_days_from_monday() as u64);
+        let month_start = today.with_day(1).unwrap();
+        match self {
+            Period::Today     => (today, today + Days::new(1)),
+            Period::Yesterday => (today - Days::new(1), today),
+            Period::ThisWeek  => (week_start, week_start + Days::new(7)),
+            Period::LastWeek  => (week_start - Days::new(7), week_start),
+            Period::ThisMonth => (month_start, month_start + Months::new(1)),
+            Period::LastMonth => (month_start - Months::new(1), month_start),
+        }
+    }
+}
+
+/// Date or date and time given on the command line.
+#[derive(Clone, Copy, Debug)]
+pub struct DateArg {
+    date: NaiveDate,
+    time: Option<NaiveTime>,
+}
+
+/// Parses "2024-09-01", "2024-09-01 08:15", "2024-09-01T08:15" or Finnish style "1.9.2024" (with optional time).
+pub fn parse_date_arg(arg: &str) -> Result<DateArg, String> {
+    let arg = arg.trim();
+    let (date_str, time_str) = match arg.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time.trim())),
+        None               => (arg, None),
+    };
+    let date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(date_str, "%d.%m.%Y"))
+        .map_err(|_| format!("Invalid date '{}', use e.g '2024-09-01' or '1.9.2024'", date_str))?;
+    let time = match time_str {
+        None       => None,
+        Some(time) => Some(NaiveTime::parse_from_str(time, "%H:%M")
+            .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+            .map_err(|_| format!("Invalid time '{}', use e.g '08:15'", time))?),
+    };
+    Ok(DateArg { date, time })
+}
+
+/// Parses past point of time for retroactive punches, e.g "08:15" (today), "yesterday 17:30",
+/// "2024-09-01 08:15" or "1.9.2024 08:15".
+pub fn parse_at_arg(arg: &str) -> Result<DateTime<FixedOffset>, String> {
+    parse_at_arg_relative(arg, Local::now())
+}
+
+/// Parses point of time like `parse_at_arg`, but relative to `now` and in its time zone.
+fn parse_at_arg_relative<Tz: TimeZone>(arg: &str, now: DateTime<Tz>) -> Result<DateTime<FixedOffset>, String> {
+    let arg = arg.trim();
+    let tz = now.timezone();
+    let today = now.date_naive();
+    let (date, time_str) = match arg.split_once(' ') {
+        Some(("today", time))     => (today, time.trim()),
+        Some(("yesterday", time)) => (today - Days::new(1), time.trim()),
+        None if !arg.contains(['-', '.']) => (today, arg),
+        _ => {
+            let date_arg = parse_date_arg(arg)?;
+            let time = date_arg.time.ok_or_else(|| format!("Time missing from '{}', use e.g '{} 08:15'", arg, arg))?;
+            return check_past(zoned_datetime(&tz, date_arg.date.and_time(time)), now, arg);
+        },
+    };
+    let time = NaiveTime::parse_from_str(time_str, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time_str, "%H:%M:%S"))
+        .map_err(|_| format!("Invalid time '{}', use e.g '08:15' or 'yesterday 17:30'", arg))?;
+    check_past(zoned_datetime(&tz, date.and_time(time)), now, arg)
+}
+
+fn check_past<Tz: TimeZone>(at: DateTime<FixedOffset>, now: DateTime<Tz>, arg: &str) -> Result<DateTime<FixedOffset>, String> {
+    match at > now {
+        true  => Err(format!("Time '{}' is in the future", arg)),
+        false => Ok(at),
+    }
+}
+
+/// Command line arguments for selecting a period, flattened into commands that need one.
+#[derive(clap::Args, Clone, Debug)]
+pub struct PeriodArgs {
+    /// Period shortcut, used only when '--from' is not given. (default: depends on the command)
+    #[arg(value_enum, value_name = "period", conflicts_with_all = ["from", "to"])]
+    pub period: Option<Period>,
+    /// Start of the period, e.g '2024-09-01' or '2024-09-01 08:00'
+    #[arg(long, value_name = "date", value_parser = parse_date_arg)]
+    pub from: Option<DateArg>,
+    /// End of the period, e.g '2024-09-30' (whole day included) or '2024-09-30 12:00'. (default: now)
+    #[arg(long, value_name = "date", value_parser = parse_date_arg, requires = "from")]
+    pub to: Option<DateArg>,
+}
+impl PeriodArgs {
+    /// Resolves the arguments into `[from, to)` range relative to `now` and in its time zone,
+    /// using `default` period if none was given.
+    pub fn resolve<Tz: TimeZone>(&self, now: DateTime<Tz>, default: Period) -> (DateTime<FixedOffset>, DateTime<FixedOffset>) {
+        let tz = now.timezone();
+        if let Some(from) = self.from {
+            let from_dt = zoned_datetime(&tz, from.date.and_time(from.time.unwrap_or(NaiveTime::MIN)));
+            let to_dt = match self.to {
+                None                                  => now.fixed_offset(),
+                Some(DateArg { date, time: None })    => zoned_datetime(&tz, (date + Days::new(1)).and_time(NaiveTime::MIN)),
+                Some(DateArg { date, time: Some(t) }) => zoned_datetime(&tz, date.and_time(t)),
+            };
+            return (from_dt, to_dt);
+        }
+        let (from, to) = self.period.unwrap_or(default).range(now.date_naive());
+        (zoned_datetime(&tz, from.and_time(NaiveTime::MIN)), zoned_datetime(&tz, to.and_time(NaiveTime::MIN)))
+    }
+}
+
+/// Converts naive time of the given time zone into timestamp with the UTC offset of that moment.
+/// Non-existent times (DST gap) are moved forward by an hour.
+pub fn zoned_datetime<Tz: TimeZone>(tz: &Tz, naive: NaiveDateTime) -> DateTime<FixedOffset> {
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&(naive + TimeDelta::hours(1))).earliest())
+        .expect("Local time does not exist")
+        .fixed_offset()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn datetime(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    fn helsinki(s: &str) -> DateTime<chrono_tz::Tz> {
+        datetime(s).with_timezone(&chrono_tz::Europe::Helsinki)
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let wednesday = date("2024-09-04");
+        assert_eq!(Period::ThisWeek.range(wednesday), (date("2024-09-02"), date("2024-09-09")));
+        assert_eq!(Period::LastWeek.range(wednesday), (date("2024-08-26"), date("2024-09-02")));
+        let sunday = date("2024-09-08");
+        assert_eq!(Period::ThisWeek.range(sunday), (date("2024-09-02"), date("2024-09-09")));
+        let monday = date("2024-09-02");
+        assert_eq!(Period::ThisWeek.range(monday), (date("2024-09-02"), date("2024-09-09")));
+    }
+
+    #[test]
+    fn months_and_days_cross_boundaries() {
+        let day = date("2024-01-31");
+        assert_eq!(Period::ThisMonth.range(day), (date("2024-01-01"), date("2024-02-01")));
+        assert_eq!(Period::LastMonth.range(day), (date("2023-12-01"), date("2024-01-01")));
+        let day = date("2024-03-01");
+        assert_eq!(Period::Yesterday.range(day), (date("2024-02-29"), date("2024-03-01")));
+        assert_eq!(Period::Today.range(day), (date("2024-03-01"), date("2024-03-02")));
+    }
+
+    #[test]
+    fn date_args_in_iso_and_finnish_style() {
+        let arg = parse_date_arg("2024-09-01").unwrap();
+        assert_eq!((arg.date, arg.time), (date("2024-09-01"), None));
+        let arg = parse_date_arg("1.9.2024 08:15").unwrap();
+        assert_eq!((arg.date, arg.time), (date("2024-09-01"), NaiveTime::from_hms_opt(8, 15, 0)));
+        let arg = parse_date_arg("2024-09-01T08:15:30").unwrap();
+        assert_eq!(arg.time, NaiveTime::from_hms_opt(8, 15, 30));
+        assert!(parse_date_arg("2024-13-01").is_err());
+        assert!(parse_date_arg("2024-09-01 25:00").is_err());
+    }
+
+    #[test]
+    fn at_args_are_past_times() {
+        let now = helsinki("2024-09-20T12:00:00+03:00");
+        assert_eq!(parse_at_arg_relative("yesterday 17:30", now), Ok(datetime("2024-09-19T17:30:00+03:00")));
+        assert_eq!(parse_at_arg_relative("00:00", now), Ok(datetime("2024-09-20T00:00:00+03:00")));
+        assert_eq!(parse_at_arg_relative("1.9.2024 08:15", now), Ok(datetime("2024-09-01T08:15:00+03:00")));
+        // Winter time has different offset than the summer time of `now`
+        assert_eq!(parse_at_arg_relative("2024-01-15 08:15", now), Ok(datetime("2024-01-15T08:15:00+02:00")));
+        assert!(parse_at_arg_relative("12:30", now).is_err());
+        assert!(parse_at_arg_relative("2999-01-01 08:00", now).is_err());
+        assert!(parse_at_arg_relative("2024-09-01", now).is_err());
+        assert!(parse_at_arg_relative("yesterday", now).is_err());
+    }
+
+    #[test]
+    fn from_and_to_resolve_into_half_open_range() {
+        let now = helsinki("2024-09-20T12:00:00+03:00");
+        let args = PeriodArgs { period: None, from: parse_date_arg("2024-09-01").ok(), to: parse_date_arg("2024-09-15").ok() };
+        assert_eq!(args.resolve(now, Period::Today), (datetime("2024-09-01T00:00:00+03:00"), datetime("2024-09-16T00:00:00+03:00")));
+        let args = PeriodArgs { period: None, from: parse_date_arg("2024-09-01 08:00").ok(), to: parse_date_arg("2024-09-15 12:00").ok() };
+        assert_eq!(args.resolve(now, Period::Today), (datetime("2024-09-01T08:00:00+03:00"), datetime("2024-09-15T12:00:00+03:00")));
+        let args = PeriodArgs { period: None, from: parse_date_arg("2024-09-01").ok(), to: None };
+        assert_eq!(args.resolve(now, Period::Today).1, now.fixed_offset());
+    }
+
+    #[test]
+    fn periods_follow_daylight_saving_time() {
+        // Clocks went forward from 03:00 to 04:00 on Sunday 2024-03-31, so the week is an hour shorter
+        let now = helsinki("2024-03-27T12:00:00+02:00");
+        let args = PeriodArgs { period: None, from: None, to: None };
+        let (from, to) = args.resolve(now, Period::ThisWeek);
+        assert_eq!((from, to), (datetime("2024-03-25T00:00:00+02:00"), datetime("2024-04-01T00:00:00+03:00")));
+        assert_eq!(to - from, TimeDelta::days(7) - TimeDelta::hours(1));
+    }
+
+    #[test]
+    fn local_times_in_dst_gap_and_overlap() {
+        let tz = chrono_tz::Europe::Helsinki;
+        let gap = date("2024-03-31").and_hms_opt(3, 30, 0).unwrap();
+        assert_eq!(zoned_datetime(&tz, gap), datetime("2024-03-31T04:30:00+03:00"));
+        // Repeated hour after clocks went back from 04:00 to 03:00 gets the earlier, i.e summer time, offset
+        let overlap = date("2024-10-27").and_hms_opt(3, 30, 0).unwrap();
+        assert_eq!(zoned_datetime(&tz, overlap), datetime("2024-10-27T03:30:00+03:00"));
+    }
+}
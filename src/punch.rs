@@ -0,0 +1,235 @@
+//
+// Typed model of the Kiho punch API responses.
+// Check `print_example_jsons` in `main.rs` for the full JSON shape these are parsed from.
+//
+// Kiho returns lots of fields that can be `null`, missing or even change type between objects
+// (e.g `code` is number for customers but string for projects), so almost everything is optional.
+//
+
+use chrono::{DateTime, FixedOffset};
+
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum PunchType {
+    BREAK,
+    LOGIN,
+    LOGOUT,
+    /// Any punch type this application does not know about.
+    #[value(skip)]
+    #[serde(other)]
+    UNKNOWN,
+}
+impl std::fmt::Display for PunchType {
+    // Using `pad` instead of `write!` makes width and alignment work, e.g `{: <6}`
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PunchType::BREAK => f.pad("BREAK"),
+            PunchType::LOGIN => f.pad("LOGIN"),
+            PunchType::LOGOUT => f.pad("LOGOUT"),
+            PunchType::UNKNOWN => f.pad("UNKNOWN"),
+        }
+    }
+}
+
+/// Envelope used by Kiho for every response, e.g `{ "result": [...] }`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KihoResponse<T> {
+    pub result: T,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Punch {
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub typ: PunchType,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub timestamp: DateTime<FixedOffset>,
+    #[serde(default)]
+    pub real_timestamp: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub customer_costcentre: Option<CustomerCostcentre>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default, rename = "device_sn")]
+    pub device_sn: Option<String>,
+    #[serde(default)]
+    pub user: Option<User>,
+    #[serde(default)]
+    pub wagecode: Option<Wagecode>,
+}
+impl Punch {
+    pub fn desc(&self) -> &str {
+        self.description.as_deref().unwrap_or("")
+    }
+    pub fn ccc_name(&self) -> &str {
+        self.customer_costcentre.as_ref()
+            .and_then(|ccc| ccc.name.as_deref())
+            .unwrap_or("")
+    }
+    pub fn ccc_id(&self) -> Option<u32> {
+        self.customer_costcentre.as_ref().map(|ccc| ccc.id)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomerCostcentre {
+    pub id: u32,
+    #[serde(default, deserialize_with = "code_string")]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "null_default")]
+    pub deleted: bool,
+    #[serde(default)]
+    pub favourited: Option<u32>,
+    #[serde(default)]
+    pub work_order_number: Option<String>,
+    #[serde(default)]
+    pub costcenter: Option<Costcenter>,
+    #[serde(default)]
+    pub customer: Option<Customer>,
+    #[serde(default)]
+    pub project: Option<Project>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Costcenter {
+    pub id: u32,
+    #[serde(default, deserialize_with = "code_string")]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "null_default")]
+    pub deleted: bool,
+    #[serde(default)]
+    pub visma_code: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Customer {
+    pub id: u32,
+    #[serde(default, deserialize_with = "code_string")]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub name_extra: Option<String>,
+    #[serde(default)]
+    pub nickname: Option<String>,
+    #[serde(default)]
+    pub identity: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub id: u32,
+    #[serde(default, deserialize_with = "code_string")]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "null_default")]
+    pub active: bool,
+    #[serde(default, deserialize_with = "null_default")]
+    pub deleted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub id: u32,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub person_number: Option<String>,
+    #[serde(default, deserialize_with = "null_default")]
+    pub teams: Vec<Team>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Team {
+    pub id: u32,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "null_default")]
+    pub is_default_team: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Wagecode {
+    pub id: u32,
+    #[serde(default, deserialize_with = "code_string")]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, rename = "type")]
+    pub typ: Option<String>,
+}
+
+/// Handles explicit `null` like a missing field, i.e gives the default value, e.g `false` or empty list.
+fn null_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + serde::Deserialize<'de>,
+{
+    let value: Option<T> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(value.unwrap_or_default())
+}
+
+/// Accepts `code` given either as JSON string or number and always stores it as string.
+fn code_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: Option<serde_json::Value> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(match value {
+        None | Some(serde_json::Value::Null)   => None,
+        Some(serde_json::Value::String(code))  => Some(code),
+        Some(other)                            => Some(other.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nulls_handled_like_missing_fields() {
+        let punch: Punch = serde_json::from_value(json!({
+            "id": 1, "type": "LOGIN", "description": null, "timestamp": "2024-09-02T08:00:00+03:00",
+            "customerCostcentre": {
+                "id": 101, "code": 9006, "deleted": null,
+                "costcenter": { "id": 2, "code": "21", "deleted": null },
+                "project": { "id": 3, "active": null, "deleted": null },
+            },
+            "user": { "id": 4, "teams": null },
+        })).unwrap();
+        let ccc = punch.customer_costcentre.unwrap();
+        assert_eq!(ccc.code.as_deref(), Some("9006"));
+        assert!(!ccc.deleted && !ccc.costcenter.unwrap().deleted);
+        assert!(!ccc.project.unwrap().active);
+        assert!(punch.user.unwrap().teams.is_empty());
+
+        let user: User = serde_json::from_value(json!({ "id": 4, "teams": [{ "id": 5, "isDefaultTeam": null }] })).unwrap();
+        assert!(!user.teams[0].is_default_team);
+    }
+
+    #[test]
+    fn unknown_punch_type_accepted() {
+        let punch: Punch = serde_json::from_value(json!({ "id": 1, "type": "SICK", "timestamp": "2024-09-02T08:00:00+03:00" })).unwrap();
+        assert_eq!(punch.typ, PunchType::UNKNOWN);
+    }
+}
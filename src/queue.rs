@@ -0,0 +1,99 @@
+//
+// Offline queue of punches that could not be sent because Kiho API was not reachable, e.g when
+// VPN is down. Queued punches keep their original JSON body, timestamps included, and are sent
+// in the original order with `sync` command.
+//
+
+use chrono::prelude::*;
+
+use crate::punch::Punch;
+use crate::{profile_file_name, CONFIG_NAME, STAMP_FORMAT};
+
+const QUEUE_NAME: &str = "queue";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueuedPunch {
+    /// When the punch was queued.
+    pub queued: String,
+    /// Why sending the punch failed.
+    pub error:  String,
+    /// `{ "newPunch": {...} }` JSON body exactly as it was tried to be sent.
+    pub body:   String,
+}
+impl QueuedPunch {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or_default()
+    }
+
+    /// Queued punch as a punch line without id, e.g for checking what the latest punch will be after syncing.
+    pub fn punch(&self) -> Option<Punch> {
+        let mut punch = self.json()["newPunch"].take();
+        punch["id"] = json!(0);
+        serde_json::from_value(punch).ok()
+    }
+
+    /// Short human readable summary, e.g "LOGIN 2024-09-01T08:15:00+03:00 'Rusting it out'".
+    pub fn summary(&self) -> String {
+        let json = self.json();
+        let punch = &json["newPunch"];
+        let desc = match punch["description"].as_str() {
+            Some(desc) => format!(" '{}'", desc),
+            None       => String::new(),
+        };
+        format!("{} {}{}", punch["type"].as_str().unwrap_or("?"), punch["timestamp"].as_str().unwrap_or("?"), desc)
+    }
+}
+
+// NOTE: Stored next to the configuration file like the state, but only when there's something queued.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PunchQueue {
+    pub punches: Vec<QueuedPunch>,
+}
+impl PunchQueue {
+    pub fn path() -> std::path::PathBuf {
+        confy::get_configuration_file_path(CONFIG_NAME, profile_file_name(QUEUE_NAME).as_str())
+            .expect("Getting queue file path failed")
+    }
+
+    pub fn load() -> std::io::Result<PunchQueue> {
+        if !PunchQueue::path().exists() {
+            return Ok(PunchQueue::default());
+        }
+        confy::load(CONFIG_NAME, profile_file_name(QUEUE_NAME).as_str())
+            .map_err(|err| std::io::Error::other(format!("Invalid offline queue '{}': {}", PunchQueue::path().display(), err)))
+    }
+
+    pub fn store(&self) -> Result<(), confy::ConfyError> {
+        confy::store(CONFIG_NAME, profile_file_name(QUEUE_NAME).as_str(), self)
+    }
+
+    pub fn push(&mut self, json_body: &serde_json::Value, error: &str) {
+        self.punches.push(QueuedPunch {
+            queued: Local::now().format(STAMP_FORMAT).to_string(),
+            error:  error.to_string(),
+            body:   json_body.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::punch::PunchType;
+
+    fn queued(body: &str) -> QueuedPunch {
+        QueuedPunch { queued: String::from("2024-09-02 08:15:00"), error: String::from("offline"), body: body.to_string() }
+    }
+
+    #[test]
+    fn queued_punch_read_as_punch_line() {
+        let login = queued(r#"{"newPunch":{"type":"LOGIN","description":"Rusting it out","customerCostcentre":{"id":101},
+            "timestamp":"2024-09-02T08:15:00+03:00","realTimestamp":"2024-09-02T08:15:00+03:00"}}"#).punch().unwrap();
+        assert_eq!(login.typ, PunchType::LOGIN);
+        assert_eq!(login.desc(), "Rusting it out");
+        assert_eq!(login.ccc_id(), Some(101));
+        assert_eq!(login.timestamp, DateTime::parse_from_rfc3339("2024-09-02T08:15:00+03:00").unwrap());
+        assert!(queued("not json").punch().is_none());
+    }
+}
@@ -0,0 +1,262 @@
+//
+// Worked hours reports built from paired sessions.
+//
+
+use chrono::prelude::*;
+use chrono::{Days, IsoWeek, TimeDelta};
+
+use crate::period::zoned_datetime;
+use crate::session::{format_duration, serialize_minutes, total_between, Session, SessionKind};
+
+/// Worked and break time of a single day.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayTotal {
+    date:     NaiveDate,
+    first_in: Option<DateTime<FixedOffset>>,
+    last_out: Option<DateTime<FixedOffset>>,
+    #[serde(rename = "workedMinutes", serialize_with = "serialize_minutes")]
+    worked:   TimeDelta,
+    #[serde(rename = "breakMinutes", serialize_with = "serialize_minutes")]
+    breaks:   TimeDelta,
+}
+
+/// Worked and break time of every day within `[from, to)` having any, days starting at midnight of the given time zone.
+pub fn day_totals<Tz: TimeZone>(tz: &Tz, sessions: &[Session], from: DateTime<FixedOffset>, to: DateTime<FixedOffset>) -> Vec<DayTotal> {
+    let mut totals = Vec::new();
+    let mut date = from.with_timezone(tz).date_naive();
+    while zoned_datetime(tz, date.and_time(NaiveTime::MIN)) < to {
+        let day_start = zoned_datetime(tz, date.and_time(NaiveTime::MIN)).max(from);
+        let day_end   = zoned_datetime(tz, (date + Days::new(1)).and_time(NaiveTime::MIN)).min(to);
+        let worked = total_between(sessions, SessionKind::Work, day_start, day_end);
+        let breaks = total_between(sessions, SessionKind::Break, day_start, day_end);
+        if worked > TimeDelta::zero() || breaks > TimeDelta::zero() {
+            let day_sessions = sessions.iter()
+                .filter(|s| s.kind == SessionKind::Work && s.duration_between(day_start, day_end) > TimeDelta::zero());
+            totals.push(DayTotal {
+                date,
+                first_in: day_sessions.clone().map(|s| s.start.max(day_start)).min(),
+                last_out: day_sessions.map(|s| s.end.min(day_end)).max(),
+                worked,
+                breaks,
+            });
+        }
+        date = date + Days::new(1);
+    }
+    totals
+}
+
+/// Worked and break time of a single ISO week.
+struct WeekTotal {
+    week:   IsoWeek,
+    worked: TimeDelta,
+    breaks: TimeDelta,
+}
+
+/// Sums up the days by ISO week, which always starts on Monday, also at the turn of the year.
+fn week_totals(days: &[DayTotal]) -> Vec<WeekTotal> {
+    let mut weeks: Vec<WeekTotal> = Vec::new();
+    for day in days {
+        let week = day.date.iso_week();
+        match weeks.last_mut() {
+            Some(total) if total.week == week => {
+                total.worked += day.worked;
+                total.breaks += day.breaks;
+            },
+            _ => weeks.push(WeekTotal { week, worked: day.worked, breaks: day.breaks }),
+        }
+    }
+    weeks
+}
+
+fn print_separator() {
+    println!("|-{:-<10}-|-{:-<3}-|-{:-<8}-|-{:-<8}-|-{:-<9}-|-{:-<9}-|", "", "", "", "", "", "");
+}
+
+fn print_total_row(title: &str, breaks: TimeDelta, worked: TimeDelta) {
+    println!("| {: <10} | {: <3} | {: <8} | {: <8} | {: >9} | {: >9} |", title, "", "", "", format_duration(breaks), format_duration(worked));
+}
+
+/// Prints per-day worked time and breaks, with per-week totals after each ISO week and the grand total.
+pub fn print_worktime_report(sessions: &[Session], from: DateTime<FixedOffset>, to: DateTime<FixedOffset>) {
+    let days = day_totals(&Local, sessions, from, to);
+    if days.is_empty() {
+        println!("NONE FOUND!");
+        return;
+    }
+    println!("| {: <10} | {: <3} | {: <8} | {: <8} | {: >9} | {: >9} |", "Date", "Day", "First In", "Last Out", "Breaks", "Worked");
+    print_separator();
+    // Weeks are in the same order as the days, so the next one is always the one ending
+    let mut weeks = week_totals(&days).into_iter();
+    for (idx, day) in days.iter().enumerate() {
+        let first_in = day.first_in.map(|t| t.format("%H:%M").to_string()).unwrap_or_default();
+        let last_out = day.last_out.map(|t| t.format("%H:%M").to_string()).unwrap_or_default();
+        println!("| {: <10} | {: <3} | {: <8} | {: <8} | {: >9} | {: >9} |", day.date.format("%d.%m.%Y"), day.date.format("%a"),
+            first_in, last_out, format_duration(day.breaks), format_duration(day.worked));
+        let week_ends = days.get(idx + 1).is_none_or(|next| next.date.iso_week() != day.date.iso_week());
+        if let Some(week) = week_ends.then(|| weeks.next()).flatten() {
+            print_separator();
+            print_total_row(&format!("Week {}", week.week.week()), week.breaks, week.worked);
+            print_separator();
+        }
+    }
+    let total_worked: TimeDelta = days.iter().map(|d| d.worked).sum();
+    let total_breaks: TimeDelta = days.iter().map(|d| d.breaks).sum();
+    print_total_row("Total", total_breaks, total_worked);
+    print_separator();
+}
+
+
+/// Worked time of a single customer cost centre.
+#[derive(Serialize)]
+pub struct CostcentreTotal {
+    #[serde(rename = "cccId")]
+    id:       Option<u32>,
+    name:     String,
+    project:  String,
+    customer: String,
+    #[serde(rename = "workedMinutes", serialize_with = "serialize_minutes")]
+    worked:   TimeDelta,
+}
+
+pub fn costcentre_totals(sessions: &[Session], from: DateTime<FixedOffset>, to: DateTime<FixedOffset>) -> Vec<CostcentreTotal> {
+    let mut totals: Vec<CostcentreTotal> = Vec::new();
+    for session in sessions.iter().filter(|s| s.kind == SessionKind::Work) {
+        let worked = session.duration_between(from, to);
+        if worked <= TimeDelta::zero() {
+            continue;
+        }
+        let ccc = session.punch.customer_costcentre.as_ref();
+        let id  = ccc.map(|ccc| ccc.id);
+        match totals.iter_mut().find(|t| t.id == id) {
+            Some(total) => total.worked += worked,
+            None => totals.push(CostcentreTotal {
+                id,
+                name:     session.punch.ccc_name().to_string(),
+                project:  ccc.and_then(|ccc| ccc.project.as_ref()).and_then(|p| p.name.clone()).unwrap_or_default(),
+                customer: ccc.and_then(|ccc| ccc.customer.as_ref()).and_then(|c| c.name.clone()).unwrap_or_default(),
+                worked,
+            }),
+        }
+    }
+    totals.sort_unstable_by_key(|t| std::cmp::Reverse(t.worked));
+    totals
+}
+
+/// Prints worked time per customer cost centre, largest first.
+pub fn print_costcentre_report(sessions: &[Session], from: DateTime<FixedOffset>, to: DateTime<FixedOffset>) {
+    let totals = costcentre_totals(sessions, from, to);
+    if totals.is_empty() {
+        println!("NONE FOUND!");
+        return;
+    }
+    let name_width     = totals.iter().map(|t| t.name.chars().count()).max().unwrap_or(0).max(16);
+    let project_width  = totals.iter().map(|t| t.project.chars().count()).max().unwrap_or(0).max(7);
+    let customer_width = totals.iter().map(|t| t.customer.chars().count()).max().unwrap_or(0).max(8);
+    let separator = format!("|-{:-<8}-|-{:-<name_width$}-|-{:-<project_width$}-|-{:-<customer_width$}-|-{:-<9}-|-{:-<5}-|", "", "", "", "", "", "");
+    let grand_total: TimeDelta = totals.iter().map(|t| t.worked).sum();
+
+    println!("| {: <8} | {: <name_width$} | {: <project_width$} | {: <customer_width$} | {: >9} | {: >5} |", "CCC ID", "Cost Centre Name", "Project", "Customer", "Worked", "Share");
+    println!("{}", separator);
+    for total in &totals {
+        let id = total.id.map(|id| id.to_string()).unwrap_or_else(|| String::from("(none)"));
+        let share = 100.0 * total.worked.num_seconds() as f64 / grand_total.num_seconds().max(1) as f64;
+        println!("| {: <8} | {: <name_width$} | {: <project_width$} | {: <customer_width$} | {: >9} | {: >4.0}% |",
+            id, total.name, total.project, total.customer, format_duration(total.worked), share);
+    }
+    println!("{}", separator);
+    println!("| {: <8} | {: <name_width$} | {: <project_width$} | {: <customer_width$} | {: >9} | {: >5} |", "Total", "", "", "", format_duration(grand_total), "");
+    println!("{}", separator);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::punch::Punch;
+    use crate::session::pair_sessions;
+
+    fn datetime(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    fn punch(id: u64, typ: &str, timestamp: &str, ccc_id: Option<u32>) -> Punch {
+        let ccc = ccc_id.map(|id| json!({
+            "id": id, "name": format!("CCC {}", id),
+            "project": { "id": 1, "name": "Rust" }, "customer": { "id": 2, "name": "Kiho" },
+        }));
+        serde_json::from_value(json!({ "id": id, "type": typ, "timestamp": timestamp, "customerCostcentre": ccc })).unwrap()
+    }
+
+    fn sessions(plines: &[Punch]) -> Vec<Session> {
+        pair_sessions(plines, datetime("2030-01-01T00:00:00+02:00"))
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn day(s: &str, worked_min: i64) -> DayTotal {
+        DayTotal {
+            date:     date(s),
+            first_in: None,
+            last_out: None,
+            worked:   TimeDelta::minutes(worked_min),
+            breaks:   TimeDelta::zero(),
+        }
+    }
+
+    #[test]
+    fn costcentre_totals_grouped_by_id_and_clipped() {
+        let sessions = sessions(&[
+            punch(1, "LOGIN",  "2024-09-02T08:00:00+03:00", Some(101)),
+            punch(2, "LOGIN",  "2024-09-02T10:00:00+03:00", Some(202)),
+            punch(3, "LOGIN",  "2024-09-02T11:00:00+03:00", None),
+            punch(4, "BREAK",  "2024-09-02T11:30:00+03:00", None),
+            punch(5, "LOGIN",  "2024-09-02T12:00:00+03:00", Some(101)),
+            punch(6, "LOGOUT", "2024-09-02T13:00:00+03:00", None),
+        ]);
+        let totals = costcentre_totals(&sessions, datetime("2024-09-02T00:00:00+03:00"), datetime("2024-09-03T00:00:00+03:00"));
+        let worked: Vec<(Option<u32>, i64)> = totals.iter().map(|t| (t.id, t.worked.num_minutes())).collect();
+        assert_eq!(worked, [(Some(101), 180), (Some(202), 60), (None, 30)]);
+        assert_eq!((totals[0].name.as_str(), totals[0].project.as_str(), totals[0].customer.as_str()), ("CCC 101", "Rust", "Kiho"));
+        assert_eq!((totals[2].name.as_str(), totals[2].project.as_str()), ("", ""));
+
+        let totals = costcentre_totals(&sessions, datetime("2024-09-02T09:00:00+03:00"), datetime("2024-09-02T12:30:00+03:00"));
+        let worked: Vec<(Option<u32>, i64)> = totals.iter().map(|t| (t.id, t.worked.num_minutes())).collect();
+        assert_eq!(worked, [(Some(101), 90), (Some(202), 60), (None, 30)]);
+    }
+
+    #[test]
+    fn day_totals_split_at_local_midnight() {
+        let tz = FixedOffset::east_opt(3 * 3600).unwrap();
+        let sessions = sessions(&[
+            punch(1, "LOGIN",  "2024-09-02T21:00:00+03:00", Some(101)),
+            punch(2, "BREAK",  "2024-09-02T23:00:00+03:00", None),
+            punch(3, "LOGIN",  "2024-09-02T23:30:00+03:00", Some(101)),
+            punch(4, "LOGOUT", "2024-09-03T02:00:00+03:00", None),
+        ]);
+        let days = day_totals(&tz, &sessions, datetime("2024-09-01T00:00:00+03:00"), datetime("2024-09-05T00:00:00+03:00"));
+        assert_eq!(days.len(), 2);
+        assert_eq!((days[0].date, days[0].worked, days[0].breaks), (date("2024-09-02"), TimeDelta::minutes(150), TimeDelta::minutes(30)));
+        assert_eq!((days[0].first_in, days[0].last_out), (Some(datetime("2024-09-02T21:00:00+03:00")), Some(datetime("2024-09-03T00:00:00+03:00"))));
+        assert_eq!((days[1].date, days[1].worked, days[1].breaks), (date("2024-09-03"), TimeDelta::minutes(120), TimeDelta::zero()));
+        assert_eq!((days[1].first_in, days[1].last_out), (Some(datetime("2024-09-03T00:00:00+03:00")), Some(datetime("2024-09-03T02:00:00+03:00"))));
+
+        // Days are those of the given zone, and clipped to the period
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let days = day_totals(&utc, &sessions, datetime("2024-09-02T22:00:00+03:00"), datetime("2024-09-03T01:00:00+03:00"));
+        let worked: Vec<(NaiveDate, i64)> = days.iter().map(|d| (d.date, d.worked.num_minutes())).collect();
+        assert_eq!(worked, [(date("2024-09-02"), 150)]);
+        assert_eq!(days[0].first_in, Some(datetime("2024-09-02T22:00:00+03:00")));
+        assert_eq!(days[0].last_out, Some(datetime("2024-09-03T01:00:00+03:00")));
+    }
+
+    #[test]
+    fn weeks_split_on_monday_also_at_turn_of_year() {
+        let days = [day("2024-12-28", 60), day("2024-12-29", 30), day("2024-12-30", 120), day("2025-01-01", 15), day("2025-01-06", 45)];
+        let weeks: Vec<(i32, u32, i64)> = week_totals(&days).iter()
+            .map(|w| (w.week.year(), w.week.week(), w.worked.num_minutes()))
+            .collect();
+        assert_eq!(weeks, [(2024, 52, 90), (2025, 1, 135), (2025, 2, 45)]);
+    }
+}
@@ -0,0 +1,144 @@
+//
+// Worktime sessions paired from punch lines.
+//
+// Every LOGIN starts a work session and every BREAK starts a break, both lasting until the next
+// punch line whatever its type. LOGOUT punch lines only end sessions. Because breaks end work
+// sessions, worked time never includes break time.
+//
+
+use chrono::{DateTime, FixedOffset, TimeDelta};
+
+use crate::punch::{Punch, PunchType};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionKind {
+    Work,
+    Break,
+}
+
+#[derive(Clone, Debug)]
+pub struct Session {
+    /// LOGIN or BREAK punch line that started the session.
+    pub punch: Punch,
+    pub kind:  SessionKind,
+    pub start: DateTime<FixedOffset>,
+    /// Sessions still going on end at the time they were paired.
+    pub end:   DateTime<FixedOffset>,
+}
+impl Session {
+    pub fn duration(&self) -> TimeDelta {
+        self.end - self.start
+    }
+
+    /// Part of the session that falls within `[from, to)`.
+    pub fn duration_between(&self, from: DateTime<FixedOffset>, to: DateTime<FixedOffset>) -> TimeDelta {
+        (self.end.min(to) - self.start.max(from)).max(TimeDelta::zero())
+    }
+}
+
+/// Pairs punch lines given in ascending order into sessions. Session still going on at the end
+/// is closed at `until`, unless it started after it.
+pub fn pair_sessions(plines_asc: &[Punch], until: DateTime<FixedOffset>) -> Vec<Session> {
+    let mut sessions: Vec<Session> = Vec::new();
+    let mut current: Option<(&Punch, SessionKind)> = None;
+    for pl in plines_asc {
+        if let Some((start, kind)) = current.take() {
+            sessions.push(Session { punch: start.clone(), kind, start: start.timestamp, end: pl.timestamp });
+        }
+        current = match pl.typ {
+            PunchType::LOGIN => Some((pl, SessionKind::Work)),
+            PunchType::BREAK => Some((pl, SessionKind::Break)),
+            _                => None,
+        };
+    }
+    if let Some((start, kind)) = current {
+        if start.timestamp <= until {
+            sessions.push(Session { punch: start.clone(), kind, start: start.timestamp, end: until });
+        }
+    }
+    sessions
+}
+
+/// Total time of `kind` sessions within `[from, to)`.
+pub fn total_between(sessions: &[Session], kind: SessionKind, from: DateTime<FixedOffset>, to: DateTime<FixedOffset>) -> TimeDelta {
+    sessions.iter()
+        .filter(|s| s.kind == kind)
+        .map(|s| s.duration_between(from, to))
+        .sum()
+}
+
+/// Serializes duration as whole minutes, e.g for JSON output.
+pub fn serialize_minutes<S: serde::Serializer>(duration: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i64(duration.num_minutes())
+}
+
+/// Formats duration like "2h 05m", ignoring seconds.
+pub fn format_duration(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    fn punch(id: u64, typ: &str, timestamp: &str) -> Punch {
+        serde_json::from_value(json!({ "id": id, "type": typ, "timestamp": timestamp })).unwrap()
+    }
+
+    #[test]
+    fn breaks_end_work_sessions() {
+        let plines = [
+            punch(1, "LOGIN",  "2024-09-02T08:00:00+03:00"),
+            punch(2, "BREAK",  "2024-09-02T11:00:00+03:00"),
+            punch(3, "LOGIN",  "2024-09-02T11:30:00+03:00"),
+            punch(4, "LOGOUT", "2024-09-02T16:00:00+03:00"),
+        ];
+        let sessions = pair_sessions(&plines, datetime("2024-09-02T18:00:00+03:00"));
+        let kinds: Vec<SessionKind> = sessions.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, [SessionKind::Work, SessionKind::Break, SessionKind::Work]);
+        assert_eq!(sessions[2].end, datetime("2024-09-02T16:00:00+03:00"));
+        let (from, to) = (datetime("2024-09-02T00:00:00+03:00"), datetime("2024-09-03T00:00:00+03:00"));
+        assert_eq!(total_between(&sessions, SessionKind::Work, from, to), TimeDelta::minutes(7 * 60 + 30));
+        assert_eq!(total_between(&sessions, SessionKind::Break, from, to), TimeDelta::minutes(30));
+    }
+
+    #[test]
+    fn active_session_ends_at_pairing_time() {
+        let plines = [punch(1, "LOGIN", "2024-09-02T08:00:00+03:00")];
+        let sessions = pair_sessions(&plines, datetime("2024-09-02T10:15:00+03:00"));
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].duration(), TimeDelta::minutes(135));
+        // Session starting after the pairing time has not started yet
+        assert!(pair_sessions(&plines, datetime("2024-09-02T07:00:00+03:00")).is_empty());
+    }
+
+    #[test]
+    fn logouts_alone_make_no_sessions() {
+        let plines = [punch(1, "LOGOUT", "2024-09-02T08:00:00+03:00"), punch(2, "LOGOUT", "2024-09-02T09:00:00+03:00")];
+        assert!(pair_sessions(&plines, datetime("2024-09-02T10:00:00+03:00")).is_empty());
+    }
+
+    #[test]
+    fn durations_clipped_to_half_open_range() {
+        let plines = [
+            punch(1, "LOGIN",  "2024-09-01T22:00:00+03:00"),
+            punch(2, "LOGOUT", "2024-09-02T02:00:00+03:00"),
+        ];
+        let sessions = pair_sessions(&plines, datetime("2024-09-03T00:00:00+03:00"));
+        let midnight = datetime("2024-09-02T00:00:00+03:00");
+        assert_eq!(sessions[0].duration_between(midnight, datetime("2024-09-03T00:00:00+03:00")), TimeDelta::hours(2));
+        assert_eq!(sessions[0].duration_between(datetime("2024-09-01T00:00:00+03:00"), midnight), TimeDelta::hours(2));
+        assert_eq!(sessions[0].duration_between(datetime("2024-09-02T02:00:00+03:00"), datetime("2024-09-02T03:00:00+03:00")), TimeDelta::zero());
+    }
+
+    #[test]
+    fn durations_formatted_without_seconds() {
+        assert_eq!(format_duration(TimeDelta::seconds(2 * 3600 + 5 * 60 + 59)), "2h 05m");
+        assert_eq!(format_duration(TimeDelta::zero()), "0h 00m");
+    }
+}
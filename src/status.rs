@@ -0,0 +1,262 @@
+//
+// Current worktime status, e.g "Working on 'Rusting it out' (CCC 101) since 09:12 — 2h 31m, today total 5h 02m".
+//
+// Status can also be formatted with a template or a preset for shell prompts and status bars.
+//
+
+use chrono::prelude::*;
+use chrono::TimeDelta;
+
+use crate::period::zoned_datetime;
+use crate::punch::{Punch, PunchType};
+use crate::session::{format_duration, pair_sessions, serialize_minutes, total_between, SessionKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkState {
+    Working,
+    Break,
+    Out,
+}
+
+impl WorkState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WorkState::Working => "working",
+            WorkState::Break   => "break",
+            WorkState::Out     => "out",
+        }
+    }
+}
+
+/// Ready-made status formats for status bars.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum StatusPreset {
+    /// JSON for waybar 'custom' module with 'return-type: json'
+    Waybar,
+    /// JSON for i3blocks block with 'format=json'
+    I3blocks,
+    /// Colored text for tmux 'status-right'
+    Tmux,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub state:       WorkState,
+    /// Description of the latest punch line, e.g task or break type.
+    pub description: String,
+    pub ccc_id:      Option<u32>,
+    pub ccc_name:    String,
+    /// Timestamp of the latest punch line, if there is one.
+    pub since:       Option<DateTime<FixedOffset>>,
+    #[serde(rename = "elapsedMinutes", serialize_with = "serialize_minutes")]
+    pub elapsed:     TimeDelta,
+    #[serde(rename = "todayMinutes", serialize_with = "serialize_minutes")]
+    pub today_total: TimeDelta,
+}
+
+impl Status {
+    /// Resolves status at `now` from the latest punch lines given in ascending order.
+    /// Today starts at midnight in the time zone of `now`.
+    pub fn from_punches<Tz: TimeZone>(plines_asc: &[Punch], now: DateTime<Tz>) -> Status {
+        let tz = now.timezone();
+        let now = now.fixed_offset();
+        let plines: Vec<Punch> = plines_asc.iter()
+            .filter(|pl| pl.timestamp <= now)
+            .cloned()
+            .collect();
+        let sessions = pair_sessions(&plines, now);
+        let today_start = zoned_datetime(&tz, now.date_naive().and_time(NaiveTime::MIN));
+        let today_total = total_between(&sessions, SessionKind::Work, today_start, now);
+        let Some(latest) = plines.last() else {
+            return Status {
+                state: WorkState::Out, description: String::new(), ccc_id: None, ccc_name: String::new(),
+                since: None, elapsed: TimeDelta::zero(), today_total,
+            };
+        };
+        let state = match latest.typ {
+            PunchType::LOGIN => WorkState::Working,
+            PunchType::BREAK => WorkState::Break,
+            _                => WorkState::Out,
+        };
+        Status {
+            state,
+            description: latest.desc().to_string(),
+            ccc_id:      latest.ccc_id(),
+            ccc_name:    latest.ccc_name().to_string(),
+            since:       Some(latest.timestamp),
+            elapsed:     now - latest.timestamp,
+            today_total,
+        }
+    }
+
+    /// Time of the latest punch line, shown without date if it's from today.
+    fn since_str(&self) -> String {
+        match self.since {
+            None                                                         => String::from("ever"),
+            Some(since) if since.date_naive() == Local::now().date_naive() => since.format("%H:%M").to_string(),
+            Some(since)                                                  => since.format("%d.%m.%Y %H:%M").to_string(),
+        }
+    }
+
+    /// Fills template placeholders `{state}`, `{desc}`, `{ccc}`, `{ccc_id}`, `{since}`, `{elapsed}` and `{today}`.
+    pub fn format(&self, template: &str) -> String {
+        template
+            .replace("{state}",   self.state.as_str())
+            .replace("{desc}",    &self.description)
+            .replace("{ccc}",     &self.ccc_name)
+            .replace("{ccc_id}",  &self.ccc_id.map(|id| id.to_string()).unwrap_or_default())
+            .replace("{since}",   &self.since_str())
+            .replace("{elapsed}", &format_duration(self.elapsed))
+            .replace("{today}",   &format_duration(self.today_total))
+    }
+
+    /// Short text for status bars, where not being logged in stands out.
+    fn short_text(&self) -> String {
+        match self.state {
+            WorkState::Working => self.format("{desc} {elapsed}"),
+            WorkState::Break   => self.format("Break {elapsed}"),
+            WorkState::Out     => String::from("Not logged in"),
+        }
+    }
+
+    pub fn preset(&self, preset: StatusPreset) -> String {
+        let color = match self.state {
+            WorkState::Working => "#a6e22e",
+            WorkState::Break   => "#e6db74",
+            WorkState::Out     => "#f92672",
+        };
+        match preset {
+            StatusPreset::Waybar => json!({
+                "text":    self.short_text(),
+                "alt":     self.state.as_str(),
+                "tooltip": self.to_string(),
+                "class":   self.state.as_str(),
+            }).to_string(),
+            StatusPreset::I3blocks => json!({
+                "full_text":  self.short_text(),
+                "short_text": format_duration(self.elapsed),
+                "color":      color,
+            }).to_string(),
+            StatusPreset::Tmux => format!("#[fg={}]{}#[default]", color, self.short_text()),
+        }
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let today = format_duration(self.today_total);
+        // Name of the cost centre is not known yet for punches still in the offline queue
+        let ccc = match (self.ccc_name.as_str(), self.ccc_id) {
+            ("", Some(id)) => format!("ccc id: {}", id),
+            (name, _)      => name.to_string(),
+        };
+        match self.state {
+            WorkState::Working => write!(f, "Working on '{}' ({}) since {} — {}, today total {}",
+                self.description, ccc, self.since_str(), format_duration(self.elapsed), today),
+            WorkState::Break   => write!(f, "On break '{}' since {} — {}, today total {}",
+                self.description, self.since_str(), format_duration(self.elapsed), today),
+            WorkState::Out     => write!(f, "Not logged in since {}, today total {}", self.since_str(), today),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helsinki(s: &str) -> DateTime<chrono_tz::Tz> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&chrono_tz::Europe::Helsinki)
+    }
+
+    fn punch(id: u64, typ: &str, timestamp: &str, desc: &str) -> Punch {
+        serde_json::from_value(json!({
+            "id": id, "type": typ, "timestamp": timestamp, "description": desc,
+            "customerCostcentre": { "id": 101, "name": "Rusting" },
+        })).unwrap()
+    }
+
+    fn day_punches() -> Vec<Punch> {
+        vec![
+            punch(1, "LOGIN",  "2024-09-02T08:00:00+03:00", "Rusting it out"),
+            punch(2, "BREAK",  "2024-09-02T11:00:00+03:00", "Lunch"),
+            punch(3, "LOGIN",  "2024-09-02T11:30:00+03:00", "Code review"),
+            punch(4, "LOGOUT", "2024-09-02T16:00:00+03:00", ""),
+        ]
+    }
+
+    #[test]
+    fn working_and_break_states() {
+        let status = Status::from_punches(&day_punches(), helsinki("2024-09-02T10:30:00+03:00"));
+        assert_eq!(status.state, WorkState::Working);
+        assert_eq!((status.description.as_str(), status.ccc_id, status.ccc_name.as_str()), ("Rusting it out", Some(101), "Rusting"));
+        assert_eq!(status.since, Some(DateTime::parse_from_rfc3339("2024-09-02T08:00:00+03:00").unwrap()));
+        assert_eq!((status.elapsed, status.today_total), (TimeDelta::minutes(150), TimeDelta::minutes(150)));
+
+        let status = Status::from_punches(&day_punches(), helsinki("2024-09-02T11:20:00+03:00"));
+        assert_eq!((status.state, status.description.as_str()), (WorkState::Break, "Lunch"));
+        assert_eq!((status.elapsed, status.today_total), (TimeDelta::minutes(20), TimeDelta::minutes(180)));
+    }
+
+    #[test]
+    fn out_state_after_logout_or_without_punches() {
+        let status = Status::from_punches(&day_punches(), helsinki("2024-09-02T17:00:00+03:00"));
+        assert_eq!((status.state, status.description.as_str()), (WorkState::Out, ""));
+        assert_eq!((status.elapsed, status.today_total), (TimeDelta::minutes(60), TimeDelta::minutes(7 * 60 + 30)));
+
+        let status = Status::from_punches(&[], helsinki("2024-09-02T17:00:00+03:00"));
+        assert_eq!((status.state, status.since), (WorkState::Out, None));
+        assert_eq!((status.elapsed, status.today_total), (TimeDelta::zero(), TimeDelta::zero()));
+    }
+
+    #[test]
+    fn today_total_starts_at_midnight() {
+        let plines = [
+            punch(1, "LOGIN",  "2024-09-02T22:00:00+03:00", "Night shift"),
+            punch(2, "LOGOUT", "2024-09-03T04:00:00+03:00", ""),
+        ];
+        // Punch lines after `now` are not known yet
+        let status = Status::from_punches(&plines, helsinki("2024-09-03T01:30:00+03:00"));
+        assert_eq!(status.state, WorkState::Working);
+        assert_eq!((status.elapsed, status.today_total), (TimeDelta::minutes(210), TimeDelta::minutes(90)));
+    }
+
+    fn working_status() -> Status {
+        Status {
+            state:       WorkState::Working,
+            description: String::from("Code review"),
+            ccc_id:      Some(101),
+            ccc_name:    String::from("Rusting"),
+            since:       Some(DateTime::parse_from_rfc3339("2024-09-02T08:00:00+03:00").unwrap()),
+            elapsed:     TimeDelta::minutes(125),
+            today_total: TimeDelta::minutes(302),
+        }
+    }
+
+    #[test]
+    fn template_placeholders_filled() {
+        let template = "{state}|{desc}|{ccc}|{ccc_id}|{since}|{elapsed}|{today}|{unknown}";
+        assert_eq!(working_status().format(template), "working|Code review|Rusting|101|02.09.2024 08:00|2h 05m|5h 02m|{unknown}");
+        let out = Status { state: WorkState::Out, description: String::new(), ccc_id: None, ccc_name: String::new(),
+            since: None, elapsed: TimeDelta::zero(), today_total: TimeDelta::zero() };
+        assert_eq!(out.format(template), "out||||ever|0h 00m|0h 00m|{unknown}");
+    }
+
+    #[test]
+    fn presets_for_status_bars() {
+        let status = working_status();
+        let waybar: serde_json::Value = serde_json::from_str(&status.preset(StatusPreset::Waybar)).unwrap();
+        assert_eq!(waybar, json!({
+            "text": "Code review 2h 05m", "alt": "working", "class": "working",
+            "tooltip": "Working on 'Code review' (Rusting) since 02.09.2024 08:00 — 2h 05m, today total 5h 02m",
+        }));
+        let i3blocks: serde_json::Value = serde_json::from_str(&status.preset(StatusPreset::I3blocks)).unwrap();
+        assert_eq!(i3blocks, json!({ "full_text": "Code review 2h 05m", "short_text": "2h 05m", "color": "#a6e22e" }));
+        assert_eq!(status.preset(StatusPreset::Tmux), "#[fg=#a6e22e]Code review 2h 05m#[default]");
+        let on_break = Status { state: WorkState::Break, ..working_status() };
+        assert_eq!(on_break.preset(StatusPreset::Tmux), "#[fg=#e6db74]Break 2h 05m#[default]");
+        let out = Status { state: WorkState::Out, ..working_status() };
+        assert_eq!(out.preset(StatusPreset::Tmux), "#[fg=#f92672]Not logged in#[default]");
+    }
+}